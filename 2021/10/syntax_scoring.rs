@@ -1,67 +1,296 @@
-use std::fs;
+use std::{collections::HashMap, env, fs};
 
 use common::utility::print_solution;
 
-fn are_pairs(lhs: char, rhs: char) -> bool {
-    match (lhs, rhs) {
-        ('(', ')') => true,
-        ('{', '}') => true,
-        ('[', ']') => true,
-        ('<', '>') => true,
-        _ => false,
+// (opener, closer, corruption score, completion score)
+const STANDARD_PAIRS: [(char, char, u64, u64); 4] = [
+    ('(', ')', 3, 1),
+    ('[', ']', 57, 2),
+    ('{', '}', 1197, 3),
+    ('<', '>', 25137, 4),
+];
+
+const COMPLETION_MULTIPLIER: u64 = 5;
+
+// Columns are 0-based character indices
+#[derive(Debug, PartialEq, Eq)]
+enum LineStatus {
+    Valid,
+    Corrupted {
+        column: usize,
+        found: char,
+        expected: char,
+    },
+    Incomplete {
+        completion: String,
+    },
+    // A closer without any open chunk
+    UnbalancedCloser {
+        column: usize,
+        found: char,
+    },
+    // A character that is neither an opener nor a closer
+    InvalidCharacter {
+        column: usize,
+        found: char,
+    },
+}
+
+struct BracketChecker {
+    closers_by_opener: HashMap<char, char>,
+    corruption_scores: HashMap<char /*closer*/, u64>,
+    completion_scores: HashMap<char /*closer*/, u64>,
+}
+
+impl BracketChecker {
+    fn new(pairs: &[(char, char, u64, u64)]) -> Result<Self, String> {
+        let mut checker = Self {
+            closers_by_opener: HashMap::new(),
+            corruption_scores: HashMap::new(),
+            completion_scores: HashMap::new(),
+        };
+        for &(opener, closer, corruption_score, completion_score) in pairs {
+            let is_used = |c| {
+                checker.closers_by_opener.contains_key(&c)
+                    || checker.corruption_scores.contains_key(&c)
+            };
+            if opener == closer || is_used(opener) || is_used(closer) {
+                return Err(format!("Ambiguous pair '{}{}'", opener, closer));
+            }
+            checker.closers_by_opener.insert(opener, closer);
+            checker.corruption_scores.insert(closer, corruption_score);
+            checker.completion_scores.insert(closer, completion_score);
+        }
+        Ok(checker)
+    }
+
+    fn standard() -> Self {
+        Self::new(&STANDARD_PAIRS).expect("Invalid standard pairs")
+    }
+
+    fn check(&self, line: &str) -> LineStatus {
+        let mut expected_closers = Vec::new();
+        for (column, c) in line.chars().enumerate() {
+            if let Some(&closer) = self.closers_by_opener.get(&c) {
+                expected_closers.push(closer);
+            } else if self.corruption_scores.contains_key(&c) {
+                match expected_closers.pop() {
+                    Some(expected) if expected == c => (),
+                    Some(expected) => {
+                        return LineStatus::Corrupted {
+                            column,
+                            found: c,
+                            expected,
+                        }
+                    }
+                    None => return LineStatus::UnbalancedCloser { column, found: c },
+                }
+            } else {
+                return LineStatus::InvalidCharacter { column, found: c };
+            }
+        }
+
+        if expected_closers.is_empty() {
+            LineStatus::Valid
+        } else {
+            LineStatus::Incomplete {
+                completion: expected_closers.iter().rev().collect(),
+            }
+        }
+    }
+
+    fn corruption_score(&self, status: &LineStatus) -> Option<u64> {
+        match status {
+            LineStatus::Corrupted { found, .. } => self.corruption_scores.get(found).copied(),
+            _ => None,
+        }
+    }
+
+    // None for lines that are not incomplete, Some(None) if the score does not fit in a u64
+    fn completion_score(&self, status: &LineStatus) -> Option<Option<u64>> {
+        match status {
+            LineStatus::Incomplete { completion } => {
+                Some(completion.chars().try_fold(0u64, |acc, c| {
+                    acc.checked_mul(COMPLETION_MULTIPLIER)?
+                        .checked_add(self.completion_scores[&c])
+                }))
+            }
+            _ => None,
+        }
     }
 }
 
-fn main() {
-    let file_content = fs::read_to_string("2021/10/input.txt").expect("Cannot read input file");
-    let lines = file_content
-        .lines()
-        .map(|line| line.chars().collect::<Vec<char>>())
-        .collect::<Vec<Vec<char>>>();
-
-    let (part1, mut part2) = lines.iter().fold((0i64, Vec::new()), |mut acc, line| {
-        let mut stack = Vec::new();
-        for c in line {
-            match c {
-                '(' | '{' | '[' | '<' => {
-                    stack.push(*c);
+fn json_string(string: &str) -> String {
+    let mut json = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn caret_line(column: usize) -> String {
+    format!("{}^", " ".repeat(column))
+}
+
+fn text_report(checker: &BracketChecker, lines: &[&str], statuses: &[LineStatus]) -> String {
+    let mut report = String::new();
+    for (index, (line, status)) in lines.iter().zip(statuses.iter()).enumerate() {
+        let (summary, column) = match status {
+            LineStatus::Valid => ("valid".to_string(), None),
+            LineStatus::Corrupted {
+                column,
+                found,
+                expected,
+            } => (
+                format!(
+                    "corrupted at column {}, expected '{}' but found '{}', score {}",
+                    column + 1,
+                    expected,
+                    found,
+                    checker.corruption_score(status).unwrap()
+                ),
+                Some(*column),
+            ),
+            LineStatus::Incomplete { completion } => (
+                match checker.completion_score(status).unwrap() {
+                    Some(score) => format!(
+                        "incomplete, completion '{}', autocomplete score {}",
+                        completion, score
+                    ),
+                    None => format!(
+                        "incomplete, completion '{}', autocomplete score overflows",
+                        completion
+                    ),
+                },
+                None,
+            ),
+            LineStatus::UnbalancedCloser { column, found } => (
+                format!("unbalanced closer '{}' at column {}", found, column + 1),
+                Some(*column),
+            ),
+            LineStatus::InvalidCharacter { column, found } => (
+                format!("invalid character '{}' at column {}", found, column + 1),
+                Some(*column),
+            ),
+        };
+        report.push_str(&format!("Line {}: {}\n{}\n", index + 1, summary, line));
+        if let Some(column) = column {
+            report.push_str(&caret_line(column));
+            report.push('\n');
+        }
+    }
+    report
+}
+
+fn json_report(checker: &BracketChecker, lines: &[&str], statuses: &[LineStatus]) -> String {
+    let entries = lines
+        .iter()
+        .zip(statuses.iter())
+        .enumerate()
+        .map(|(index, (line, status))| {
+            let mut fields = vec![
+                format!("\"line\": {}", index + 1),
+                format!("\"text\": {}", json_string(line)),
+            ];
+            let char_field =
+                |name, c: &char| format!("\"{}\": {}", name, json_string(&c.to_string()));
+            match status {
+                LineStatus::Valid => fields.push("\"status\": \"valid\"".to_string()),
+                LineStatus::Corrupted {
+                    column,
+                    found,
+                    expected,
+                } => {
+                    fields.push("\"status\": \"corrupted\"".to_string());
+                    fields.push(format!("\"column\": {}", column + 1));
+                    fields.push(char_field("expected", expected));
+                    fields.push(char_field("found", found));
+                    fields.push(format!(
+                        "\"score\": {}",
+                        checker.corruption_score(status).unwrap()
+                    ));
                 }
-                ')' | '}' | ']' | '>' => {
-                    let opening = stack.pop().unwrap();
-                    if !are_pairs(opening, *c) {
-                        acc.0 += match c {
-                            ')' => 3,
-                            ']' => 57,
-                            '}' => 1197,
-                            '>' => 25137,
-                            _ => panic!("Unexpected char"),
-                        };
-                        stack.clear();
-                        break;
+                LineStatus::Incomplete { completion } => {
+                    fields.push("\"status\": \"incomplete\"".to_string());
+                    fields.push(format!("\"completion\": {}", json_string(completion)));
+                    match checker.completion_score(status).unwrap() {
+                        Some(score) => fields.push(format!("\"score\": {}", score)),
+                        None => fields.push("\"score\": null, \"overflow\": true".to_string()),
                     }
                 }
-                _ => panic!("Unexpected char"),
-            };
-        }
-        if !stack.is_empty() {
-            let autocomplete_score: i64 = stack
-                .iter()
-                .rev()
-                .map(|c| match c {
-                    '(' => 1,
-                    '{' => 3,
-                    '[' => 2,
-                    '<' => 4,
-                    _ => panic!("Unexpected char"),
-                })
-                .fold(0, |acc, p| acc * 5 + p);
-
-            acc.1.push(autocomplete_score);
+                LineStatus::UnbalancedCloser { column, found } => {
+                    fields.push("\"status\": \"unbalanced_closer\"".to_string());
+                    fields.push(format!("\"column\": {}", column + 1));
+                    fields.push(char_field("found", found));
+                }
+                LineStatus::InvalidCharacter { column, found } => {
+                    fields.push("\"status\": \"invalid_character\"".to_string());
+                    fields.push(format!("\"column\": {}", column + 1));
+                    fields.push(char_field("found", found));
+                }
+            }
+            format!("  {{{}}}", fields.join(", "))
+        })
+        .collect::<Vec<String>>();
+    format!("[\n{}\n]\n", entries.join(",\n"))
+}
+
+fn part1(checker: &BracketChecker, statuses: &[LineStatus]) -> u64 {
+    statuses
+        .iter()
+        .filter_map(|status| checker.corruption_score(status))
+        .sum()
+}
+
+fn part2(checker: &BracketChecker, statuses: &[LineStatus]) -> Result<u64, String> {
+    let mut scores = Vec::new();
+    for (index, status) in statuses.iter().enumerate() {
+        match checker.completion_score(status) {
+            Some(Some(score)) => scores.push(score),
+            Some(None) => return Err(format!("the score of line {} overflows", index + 1)),
+            None => (),
         }
-        acc
+    }
+    scores.sort_unstable();
+    scores
+        .get(scores.len() / 2)
+        .copied()
+        .ok_or_else(|| "there are no incomplete lines".to_string())
+}
+
+fn main() {
+    // --report prints a text report, --report json PATH writes a JSON one to a file
+    let mut args = env::args().skip_while(|arg| arg != "--report");
+    let report_format = args.next().map(|_| match args.next() {
+        Some(format) if format == "json" => Some(args.next().expect("Missing report path")),
+        Some(format) => panic!("Unknown report format '{}'", format),
+        None => None,
     });
-    part2.sort();
 
-    print_solution(1, part1);
-    print_solution(2, part2[part2.len() / 2]);
+    let file_content = fs::read_to_string("2021/10/input.txt").expect("Cannot read input file");
+    let checker = BracketChecker::standard();
+    let lines = file_content.lines().collect::<Vec<&str>>();
+    let statuses = lines
+        .iter()
+        .map(|line| checker.check(line))
+        .collect::<Vec<LineStatus>>();
+
+    match report_format {
+        None => (),
+        Some(None) => print!("{}", text_report(&checker, &lines, &statuses)),
+        Some(Some(path)) => fs::write(path, json_report(&checker, &lines, &statuses))
+            .expect("Cannot write report file"),
+    }
+
+    print_solution(1, part1(&checker, &statuses));
+    match part2(&checker, &statuses) {
+        Ok(score) => print_solution(2, score),
+        Err(reason) => print_solution(2, format!("unavailable, {}", reason)),
+    }
 }
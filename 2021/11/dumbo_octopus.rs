@@ -1,104 +1,261 @@
-use std::{collections::HashSet, fs};
+use std::{env, fs, path::Path, str::FromStr, thread, time::Duration};
 
+use common::automaton::{Automaton, CascadeRule};
+use common::grid::{Grid, ALL_OFFSETS};
 use common::utility::print_solution;
 
-const NEIGHBOR_OFFSETS: [(i8, i8); 8] = [
-    (-1, -1),
-    (0, -1),
-    (1, -1),
-    (-1, 0),
-    (1, 0),
-    (-1, 1),
-    (0, 1),
-    (1, 1),
-];
-
-fn get_neighbor_fields(height: usize, width: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
-    let mut neighbors = Vec::new();
-
-    for offset in &NEIGHBOR_OFFSETS {
-        let (r, c) = (
-            row.wrapping_add(offset.0 as usize),
-            col.wrapping_add(offset.1 as usize),
-        );
-        if r < height && c < width {
-            neighbors.push((r, c));
-        }
+const FLASH_THRESHOLD: usize = 9;
+
+struct OctopusRule {
+    threshold: usize,
+}
+
+impl CascadeRule for OctopusRule {
+    type Cell = usize;
+
+    fn charge(&self, energy: &mut usize) {
+        *energy += 1;
     }
 
-    neighbors
+    fn is_triggered(&self, energy: &usize) -> bool {
+        *energy > self.threshold
+    }
+
+    fn propagate(&self, energy: &mut usize) {
+        *energy += 1;
+    }
+
+    fn reset(&self, energy: &mut usize) {
+        *energy = 0;
+    }
+}
+
+// Steps simulated by default before giving up on finding a repeated state
+const MAX_STEPS: usize = 1_000_000;
+
+// The simulation is deterministic, so it ends up in a cycle: the state after `cycle_start` steps
+// is the same as after `cycle_start + cycle_length` steps.
+struct Simulation {
+    // flashes[i] is the number of flashes in step i + 1, covering every step until the first
+    // repetition of a state
+    flashes: Vec<usize>,
+    cycle_start: usize,
+    cycle_length: usize,
+    octopus_count: usize,
 }
 
-fn step(octs: &mut Vec<Vec<usize>>) -> usize {
-    let mut flashed = HashSet::new();
-    let mut to_flash = Vec::new();
-    let height = octs.len();
-    let width = octs[0].len();
-
-    for r in 0..height {
-        for c in 0..width {
-            let o = &mut octs[r][c];
-            *o += 1;
-            if *o > 9 {
-                to_flash.push((r, c));
+impl Simulation {
+    // Brent's cycle detection, which keeps a single earlier state instead of all of them. Fails
+    // if no state repeats within `max_steps` steps.
+    fn run(octopuses: &Grid<usize>, rule: &OctopusRule, max_steps: usize) -> Result<Self, String> {
+        // The hare compares its state with the one the tortoise saved at the last power of two,
+        // which finds the cycle length after at most 2 * (cycle_start + cycle_length) steps
+        let mut hare = Automaton::new(octopuses.clone(), &ALL_OFFSETS);
+        let mut tortoise = octopuses.clone();
+        let mut flashes = Vec::new();
+        let (mut power, mut cycle_length) = (1, 0);
+        loop {
+            if hare.steps() == max_steps {
+                return Err(format!("no state repeats within {} steps", max_steps));
+            }
+            flashes.push(hare.cascade_step(rule).len());
+            cycle_length += 1;
+            if hare.grid() == &tortoise {
+                break;
+            }
+            if cycle_length == power {
+                tortoise = hare.grid().clone();
+                power *= 2;
+                cycle_length = 0;
             }
         }
+
+        // With the hare a cycle ahead, both meet at the first state of the cycle
+        let mut tortoise = Automaton::new(octopuses.clone(), &ALL_OFFSETS);
+        let mut hare = Automaton::new(octopuses.clone(), &ALL_OFFSETS);
+        for _ in 0..cycle_length {
+            hare.cascade_step(rule);
+        }
+        while tortoise.grid() != hare.grid() {
+            tortoise.cascade_step(rule);
+            hare.cascade_step(rule);
+        }
+        let cycle_start = tortoise.steps();
+        flashes.truncate(cycle_start + cycle_length);
+        Ok(Self {
+            flashes,
+            cycle_start,
+            cycle_length,
+            octopus_count: octopuses.width() * octopuses.height(),
+        })
     }
 
-    while !to_flash.is_empty() {
-        let (r, c) = to_flash.pop().unwrap();
-        for (nr, nc) in get_neighbor_fields(height, width, r, c) {
-            let o = &mut octs[nr][nc];
-            *o += 1;
-            if *o == 10 && !flashed.contains(&(nr, nc)) {
-                to_flash.push((nr, nc));
-            }
+    // Every step of the cycle is simulated, so if the octopuses haven't synchronised until the
+    // first repetition, they never will
+    fn first_synchronisation(&self) -> Option<usize> {
+        self.flashes
+            .iter()
+            .position(|&flashes| flashes == self.octopus_count)
+            .map(|index| index + 1)
+    }
+
+    fn flashes_after(&self, steps: u64) -> u128 {
+        let sum = |range: std::ops::Range<usize>| {
+            self.flashes[range]
+                .iter()
+                .map(|&flashes| flashes as u128)
+                .sum::<u128>()
+        };
+        if steps <= self.flashes.len() as u64 {
+            return sum(0..steps as usize);
+        }
+
+        let cycle_steps = steps - self.cycle_start as u64;
+        let (full_cycles, remaining_steps) = (
+            cycle_steps / self.cycle_length as u64,
+            (cycle_steps % self.cycle_length as u64) as usize,
+        );
+        sum(0..self.cycle_start)
+            + full_cycles as u128 * sum(self.cycle_start..self.cycle_start + self.cycle_length)
+            + sum(self.cycle_start..self.cycle_start + remaining_steps)
+    }
+}
+
+fn part1(simulation: &Simulation) -> u128 {
+    simulation.flashes_after(100)
+}
+
+fn part2(simulation: &Simulation) -> Option<usize> {
+    simulation.first_synchronisation()
+}
+
+struct AnimationOptions {
+    // Redraw the grid in the terminal
+    terminal: bool,
+    frames_per_second: u32,
+    // Steps are 1-based and the range is inclusive
+    first_step: usize,
+    last_step: usize,
+    // Directory to write every frame into as a separate text file
+    frames_dir: Option<String>,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        Self {
+            terminal: false,
+            frames_per_second: 10,
+            first_step: 1,
+            last_step: 100,
+            frames_dir: None,
         }
-        flashed.insert((r, c));
     }
-    flashed.iter().for_each(|&(r, c)| octs[r][c] = 0);
-    flashed.len()
 }
 
-fn part1(octopuses: &Vec<Vec<usize>>) -> usize {
-    let mut octs = octopuses.clone();
-    let mut flash_count = 0;
-    for _step in 0..100 {
-        flash_count += step(&mut octs);
+// Flashed octopuses are highlighted in the terminal and marked with '*' in the text frames
+fn render_frame(grid: &Grid<usize>, flashed: &[(usize, usize)], ansi: bool) -> String {
+    let mut frame = String::new();
+    for row in 0..grid.height() {
+        for col in 0..grid.width() {
+            let pos = (row, col);
+            match (flashed.contains(&pos), ansi) {
+                (true, true) => frame.push_str(&format!("\x1b[1;93m{}\x1b[0m", grid[pos])),
+                (true, false) => frame.push('*'),
+                (false, _) => frame.push_str(&grid[pos].to_string()),
+            }
+        }
+        frame.push('\n');
     }
-    flash_count
+    frame
 }
 
-fn part2(octopuses: &Vec<Vec<usize>>) -> usize {
-    let mut octs = octopuses.clone();
-    let height = octs.len();
-    let width = octs[0].len();
-    let max_flash_count = width * height;
-    let mut step_counter = 0;
-    loop {
-        step_counter += 1;
-        if step(&mut octs) == max_flash_count {
-            return step_counter;
+fn animate(octopuses: &Grid<usize>, rule: &OctopusRule, options: &AnimationOptions) {
+    if let Some(dir) = &options.frames_dir {
+        fs::create_dir_all(dir).expect("Cannot create frames directory");
+    }
+    let frame_duration = Duration::from_secs(1) / options.frames_per_second.max(1);
+
+    let mut automaton = Automaton::new(octopuses.clone(), &ALL_OFFSETS);
+    automaton.add_hook(|event| {
+        if event.step < options.first_step || event.step > options.last_step {
+            return;
         }
+        if options.terminal {
+            // Clear the screen and move the cursor to the top left corner
+            print!("\x1b[2J\x1b[H");
+            print!("{}", render_frame(event.grid, event.triggered, true));
+            println!("Step {}, {} flashes", event.step, event.triggered.len());
+            thread::sleep(frame_duration);
+        }
+        if let Some(dir) = &options.frames_dir {
+            let path = Path::new(dir).join(format!("step_{:05}.txt", event.step));
+            let frame = format!(
+                "Step {}, {} flashes\n{}",
+                event.step,
+                event.triggered.len(),
+                render_frame(event.grid, event.triggered, false)
+            );
+            fs::write(path, frame).expect("Cannot write frame");
+        }
+    });
+    while automaton.steps() < options.last_step {
+        automaton.cascade_step(rule);
     }
 }
 
+fn parse_next<T: FromStr>(args: &mut impl Iterator<Item = String>, name: &str) -> T {
+    args.next()
+        .and_then(|value| value.parse::<T>().ok())
+        .unwrap_or_else(|| panic!("Invalid {}", name))
+}
+
 fn main() {
+    let mut args = env::args().skip(1);
+    let mut flashes_after = None;
+    let mut max_steps = MAX_STEPS;
+    let mut animation = AnimationOptions::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--flashes-after" => flashes_after = Some(parse_next(&mut args, "step count")),
+            // Gives up on finding a repeated state after this many steps
+            "--max-steps" => max_steps = parse_next(&mut args, "step limit"),
+            "--animate" => animation.terminal = true,
+            "--fps" => animation.frames_per_second = parse_next(&mut args, "frame rate"),
+            "--from" => animation.first_step = parse_next(&mut args, "first step"),
+            "--to" => animation.last_step = parse_next(&mut args, "last step"),
+            "--frames-dir" => {
+                animation.frames_dir = Some(args.next().expect("Missing frames directory"))
+            }
+            _ => panic!("Unknown argument '{}'", arg),
+        }
+    }
+
     let file_content = fs::read_to_string("2021/11/input.txt").expect("Cannot read input file");
-    let octopuses = file_content
-        .lines()
-        .map(|line| {
-            line.chars()
-                .map(|c| {
-                    c.to_digit(10)
-                        .expect("Invalid octopus energy")
-                        .try_into()
-                        .expect("Invalid octopus energy")
-                })
-                .collect::<Vec<usize>>()
-        })
-        .collect::<Vec<Vec<usize>>>();
+    let octopuses = Grid::parse_digits(&file_content).expect("Invalid octopus energy");
+    let rule = OctopusRule {
+        threshold: FLASH_THRESHOLD,
+    };
+    if animation.terminal || animation.frames_dir.is_some() {
+        animate(&octopuses, &rule, &animation);
+    }
+    let simulation = Simulation::run(&octopuses, &rule, max_steps).unwrap_or_else(|error| {
+        panic!("Cannot find the cycle of the simulation: {}", error);
+    });
 
-    print_solution(1, part1(&octopuses));
-    print_solution(2, part2(&octopuses));
+    print_solution(1, part1(&simulation));
+    match part2(&simulation) {
+        Some(step) => print_solution(2, step),
+        None => print_solution(2, "never, the octopuses don't synchronise"),
+    }
+    println!(
+        "The states repeat from step {} with a cycle of {} steps",
+        simulation.cycle_start, simulation.cycle_length
+    );
+    if let Some(steps) = flashes_after {
+        println!(
+            "Flashes after {} steps: {}",
+            steps,
+            simulation.flashes_after(steps)
+        );
+    }
 }
@@ -1,176 +1,595 @@
-use std::{
-    collections::{HashMap, HashSet},
-    fs,
-};
+use std::{collections::HashMap, env, fs};
 
 use common::utility::print_solution;
 
-fn is_full_uppercase(start: &str) -> bool {
-    start.chars().all(|c| c.is_uppercase())
-}
-
-fn bfs(
-    cave_infos: &HashMap<usize, (bool, Vec<usize>, &str)>,
-    cave: usize,
-    end: usize,
-    visited: &mut HashSet<usize>,
-) -> usize {
-    if cave == end {
-        return 1;
-    }
-    let cave_info = cave_infos.get(&cave).unwrap();
-    if !cave_info.0 {
-        visited.insert(cave);
-    }
-    let mut res = 0;
-    for next_cave in &cave_info.1 {
-        if visited.contains(next_cave) {
-            continue;
-        }
-        res += bfs(&cave_infos, *next_cave, end, visited);
-    }
-    visited.remove(&cave);
-    res
-}
-
-fn bfs2(
-    cave_infos: &HashMap<usize, (bool, Vec<usize>, &str)>,
-    cave: usize,
-    start: usize,
-    end: usize,
-    visited: &mut HashSet<usize>,
-    joker: Option<usize>,
-) -> usize {
-    let current_cave_is_joker = joker == Some(cave);
-    if cave == end {
-        return 1;
-    }
-    let cave_info = cave_infos.get(&cave).unwrap();
-    if !cave_info.0 {
-        visited.insert(cave);
-    }
-    let mut res = 0;
-    for next_cave in &cave_info.1 {
-        let mut new_joker = joker;
-        if visited.contains(next_cave) {
-            if new_joker.is_none() && *next_cave != start && *next_cave != end {
-                new_joker = Some(*next_cave);
+const START: &str = "start";
+const END: &str = "end";
+
+type CaveId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CaveSize {
+    // Can be visited any number of times
+    Big,
+    // Can be visited at most once, apart from the revisits allowed in part 2
+    Small,
+}
+
+struct Cave {
+    name: String,
+    size: CaveSize,
+    neighbors: Vec<CaveId>,
+}
+
+struct CaveGraph {
+    caves: Vec<Cave>,
+    start: CaveId,
+    end: CaveId,
+}
+
+impl CaveGraph {
+    fn parse(input: &str) -> Result<Self, String> {
+        let mut caves: Vec<Cave> = Vec::new();
+        let mut ids = HashMap::new();
+        let mut get_cave_id = |name: &str, caves: &mut Vec<Cave>| {
+            *ids.entry(name.to_string()).or_insert_with(|| {
+                let size = if name.chars().all(|c| c.is_uppercase()) {
+                    CaveSize::Big
+                } else {
+                    CaveSize::Small
+                };
+                caves.push(Cave {
+                    name: name.to_string(),
+                    size,
+                    neighbors: Vec::new(),
+                });
+                caves.len() - 1
+            })
+        };
+
+        for line in input.lines() {
+            let (src_name, dst_name) = line
+                .split_once('-')
+                .ok_or_else(|| format!("Invalid connection '{}'", line))?;
+            let src = get_cave_id(src_name, &mut caves);
+            let dst = get_cave_id(dst_name, &mut caves);
+            // Any cycle through only big caves contains a connection between two big caves (or
+            // a big cave and itself), which could be walked back and forth forever
+            if caves[src].size == CaveSize::Big && caves[dst].size == CaveSize::Big {
+                return Err(format!(
+                    "Big caves '{}' and '{}' are connected, there would be infinitely many paths",
+                    src_name, dst_name
+                ));
+            }
+            caves[src].neighbors.push(dst);
+            caves[dst].neighbors.push(src);
+        }
+
+        let find_cave = |name| {
+            caves
+                .iter()
+                .position(|cave| cave.name == name)
+                .ok_or_else(|| format!("Missing '{}' cave", name))
+        };
+        let (start, end) = (find_cave(START)?, find_cave(END)?);
+        Ok(Self { caves, start, end })
+    }
+
+    fn cave(&self, id: CaveId) -> &Cave {
+        &self.caves[id]
+    }
+
+    fn is_big(&self, id: CaveId) -> bool {
+        self.cave(id).size == CaveSize::Big
+    }
+
+    fn paths(&self, budget: RevisitBudget) -> Paths<'_> {
+        Paths {
+            graph: self,
+            budget,
+            path: Vec::new(),
+            visits: vec![0; self.caves.len()],
+            revisited_caves: 0,
+            started: false,
+        }
+    }
+
+    // Graphviz representation: start and end are highlighted, big caves are boxes and small
+    // caves are circles
+    fn to_dot(&self) -> String {
+        let mut dot = String::from("graph caves {\n");
+        for (id, cave) in self.caves.iter().enumerate() {
+            let style = if id == self.start {
+                "shape=doublecircle, style=filled, fillcolor=palegreen"
+            } else if id == self.end {
+                "shape=doublecircle, style=filled, fillcolor=salmon"
+            } else if cave.size == CaveSize::Big {
+                "shape=box, style=bold"
             } else {
-                continue;
+                "shape=circle"
+            };
+            dot.push_str(&format!("    \"{}\" [{}];\n", cave.name, style));
+        }
+        for (id, cave) in self.caves.iter().enumerate() {
+            for &neighbor in cave.neighbors.iter().filter(|&&neighbor| id < neighbor) {
+                dot.push_str(&format!(
+                    "    \"{}\" -- \"{}\";\n",
+                    cave.name,
+                    self.cave(neighbor).name
+                ));
             }
         }
-        res += bfs2(&cave_infos, *next_cave, start, end, visited, new_joker);
+        dot.push_str("}\n");
+        dot
     }
-    if !current_cave_is_joker {
-        visited.remove(&cave);
+
+    fn format_path(&self, path: &[CaveId]) -> String {
+        path.iter()
+            .map(|&id| self.cave(id).name.as_str())
+            .collect::<Vec<&str>>()
+            .join(",")
     }
-    res
 }
 
-fn part1(start: usize, end: usize, cave_infos: &HashMap<usize, (bool, Vec<usize>, &str)>) -> usize {
-    bfs(cave_infos, start, end, &mut HashSet::new())
+// At most `caves` different small caves can be revisited, each of them at most `times` times.
+// Start and end can't be revisited.
+#[derive(Clone, Copy)]
+struct RevisitBudget {
+    caves: usize,
+    times: usize,
+}
+
+const PART1_BUDGET: RevisitBudget = RevisitBudget { caves: 0, times: 0 };
+const PART2_BUDGET: RevisitBudget = RevisitBudget { caves: 1, times: 1 };
+
+// Depth-first enumeration of the paths from start to end with an explicit stack, so it works
+// for paths longer than what the call stack could handle
+struct Paths<'a> {
+    graph: &'a CaveGraph,
+    budget: RevisitBudget,
+    // The caves of the current path with the index of their next neighbor to try
+    path: Vec<(CaveId, usize)>,
+    visits: Vec<usize>,
+    // The number of small caves that are visited more than once on the current path
+    revisited_caves: usize,
+    started: bool,
 }
 
-fn part1_unwinded(
-    start: usize,
-    end: usize,
-    cave_infos: &HashMap<usize, (bool /*is_big*/, Vec<usize>, &str)>,
-) -> usize {
-    let mut visited = HashSet::new();
-    let mut stack = Vec::new();
-    let mut caves_to_visit = vec![start];
-    let mut res = 1;
-    while !caves_to_visit.is_empty() {
-        let cave = caves_to_visit.pop().unwrap();
-        let cave_info = cave_infos.get(&cave).unwrap();
-        println!("Cave: {} ({})", cave_info.2, cave);
-        if cave == end {
-            res += 1;
-            println!("\tend reached");
-            continue;
+impl<'a> Paths<'a> {
+    fn can_visit(&self, cave: CaveId) -> bool {
+        if cave == self.graph.start {
+            return false;
         }
+        if self.graph.is_big(cave) || self.visits[cave] == 0 {
+            return true;
+        }
+        let revisits = self.visits[cave] - 1;
+        cave != self.graph.end
+            && revisits < self.budget.times
+            && (revisits > 0 || self.revisited_caves < self.budget.caves)
+    }
 
-        if !cave_info.0 {
-            println!("\tPushing cave to visited and stack");
-            visited.insert(cave);
-            stack.push(cave);
+    fn push(&mut self, cave: CaveId) {
+        self.visits[cave] += 1;
+        if self.visits[cave] == 2 && !self.graph.is_big(cave) {
+            self.revisited_caves += 1;
         }
+        self.path.push((cave, 0));
+    }
 
-        let mut next_caves = cave_info
-            .1
-            .iter()
-            .filter(|c| !visited.contains(c))
-            .map(|c| *c)
-            .collect::<Vec<usize>>();
+    fn pop(&mut self) {
+        let (cave, _next_neighbor) = self.path.pop().unwrap();
+        if self.visits[cave] == 2 && !self.graph.is_big(cave) {
+            self.revisited_caves -= 1;
+        }
+        self.visits[cave] -= 1;
+    }
 
-        println!("\tNext caves: {:?}", next_caves);
-        if !next_caves.is_empty() {
-            caves_to_visit.append(&mut next_caves);
-            println!("\tContinue");
-            continue;
+    // Advances to the next path, after which the top of the stack is the end cave. Returns false
+    // if there are no more paths.
+    fn advance(&mut self) -> bool {
+        if !self.started {
+            self.started = true;
+            self.push(self.graph.start);
+        } else if !self.path.is_empty() {
+            // Step back from the end cave of the previous path
+            self.pop();
         }
 
-        println!("\tRemoving cave from visited and stack");
-        let cave_to_return_from = stack.pop().unwrap();
-        visited.remove(&cave_to_return_from);
+        while let Some(&(cave, next_neighbor)) = self.path.last() {
+            if cave == self.graph.end {
+                return true;
+            }
+
+            match self.graph.cave(cave).neighbors.get(next_neighbor) {
+                Some(&neighbor) => {
+                    self.path.last_mut().unwrap().1 += 1;
+                    if self.can_visit(neighbor) {
+                        self.push(neighbor);
+                    }
+                }
+                None => self.pop(),
+            }
+        }
+        false
+    }
+}
+
+impl<'a> Iterator for Paths<'a> {
+    type Item = Vec<CaveId>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.advance() {
+            Some(self.path.iter().map(|&(cave, _)| cave).collect())
+        } else {
+            None
+        }
+    }
+}
+
+// Counts without memoisation or collecting the paths
+fn count_paths_iterative(graph: &CaveGraph, budget: RevisitBudget) -> u64 {
+    let mut paths = graph.paths(budget);
+    let mut count = 0;
+    while paths.advance() {
+        count += 1;
+    }
+    count
+}
+
+// The remaining revisits: the number of small caves that can still be chosen for revisiting,
+// and the remaining revisits of the already chosen ones ordered by cave
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct RemainingRevisits {
+    unused_caves: usize,
+    chosen_caves: Vec<(CaveId, usize)>,
+}
+
+impl RemainingRevisits {
+    fn revisit(&self, cave: CaveId, budget: RevisitBudget) -> Option<Self> {
+        let mut remaining = self.clone();
+        match remaining
+            .chosen_caves
+            .binary_search_by_key(&cave, |&(cave, _times)| cave)
+        {
+            Ok(index) if remaining.chosen_caves[index].1 > 0 => {
+                remaining.chosen_caves[index].1 -= 1;
+            }
+            Ok(_) => return None,
+            Err(index) if remaining.unused_caves > 0 && budget.times > 0 => {
+                remaining.unused_caves -= 1;
+                remaining
+                    .chosen_caves
+                    .insert(index, (cave, budget.times - 1));
+            }
+            Err(_) => return None,
+        }
+        Some(remaining)
+    }
+}
+
+// Set of small caves, growing with the number of small caves
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct VisitedCaves(Vec<u64>);
+
+impl VisitedCaves {
+    fn new(small_caves: usize) -> Self {
+        Self(vec![0; small_caves.div_ceil(u64::BITS as usize)])
+    }
+
+    fn contains(&self, bit: usize) -> bool {
+        self.0[bit / u64::BITS as usize] >> (bit % u64::BITS as usize) & 1 == 1
+    }
+
+    fn with(&self, bit: usize) -> Self {
+        let mut visited = self.clone();
+        visited.0[bit / u64::BITS as usize] |= 1 << (bit % u64::BITS as usize);
+        visited
     }
-    res
 }
 
-fn part2(
-    start: usize,
-    end: usize,
-    cave_infos: &HashMap<usize, (bool /*is_big*/, Vec<usize>, &str)>,
-) -> usize {
-    bfs2(cave_infos, start, start, end, &mut HashSet::new(), None)
+type SearchState = (CaveId, VisitedCaves, RemainingRevisits);
+
+struct PathCounter<'a> {
+    graph: &'a CaveGraph,
+    budget: RevisitBudget,
+    // Bit of the small caves in the visited set
+    small_cave_bits: Vec<Option<usize>>,
+    small_caves: usize,
+    cache: HashMap<SearchState, u64>,
+}
+
+impl<'a> PathCounter<'a> {
+    fn new(graph: &'a CaveGraph, budget: RevisitBudget) -> Self {
+        let mut small_caves = 0;
+        let small_cave_bits = (0..graph.caves.len())
+            .map(|cave| {
+                if graph.is_big(cave) {
+                    return None;
+                }
+                small_caves += 1;
+                Some(small_caves - 1)
+            })
+            .collect::<Vec<Option<usize>>>();
+        Self {
+            graph,
+            budget,
+            small_cave_bits,
+            small_caves,
+            cache: HashMap::new(),
+        }
+    }
+
+    fn count(&mut self) -> u64 {
+        let start = self.graph.start;
+        let remaining = RemainingRevisits {
+            unused_caves: self.budget.caves,
+            chosen_caves: Vec::new(),
+        };
+        let visited =
+            VisitedCaves::new(self.small_caves).with(self.small_cave_bits[start].unwrap());
+        self.count_from((start, visited, remaining))
+    }
+
+    fn count_from(&mut self, state: SearchState) -> u64 {
+        let (cave, visited, remaining) = &state;
+        if *cave == self.graph.end {
+            return 1;
+        }
+        if let Some(&count) = self.cache.get(&state) {
+            return count;
+        }
+
+        let mut count = 0;
+        for &next_cave in &self.graph.cave(*cave).neighbors {
+            let next_state = match self.small_cave_bits[next_cave] {
+                None => (next_cave, visited.clone(), remaining.clone()),
+                Some(bit) if !visited.contains(bit) => {
+                    (next_cave, visited.with(bit), remaining.clone())
+                }
+                Some(_) if next_cave == self.graph.start || next_cave == self.graph.end => continue,
+                Some(_) => match remaining.revisit(next_cave, self.budget) {
+                    Some(remaining) => (next_cave, visited.clone(), remaining),
+                    None => continue,
+                },
+            };
+            count += self.count_from(next_state);
+        }
+        self.cache.insert(state, count);
+        count
+    }
+}
+
+fn count_paths(graph: &CaveGraph, budget: RevisitBudget) -> u64 {
+    PathCounter::new(graph, budget).count()
+}
+
+fn part1(graph: &CaveGraph) -> u64 {
+    count_paths_iterative(graph, PART1_BUDGET)
+}
+
+fn part1_memoised(graph: &CaveGraph) -> u64 {
+    count_paths(graph, PART1_BUDGET)
+}
+
+fn part2(graph: &CaveGraph) -> u64 {
+    count_paths_iterative(graph, PART2_BUDGET)
+}
+
+fn part2_memoised(graph: &CaveGraph) -> u64 {
+    count_paths(graph, PART2_BUDGET)
+}
+
+// Cave names can only contain letters, as a digit would make a big cave small
+fn letter_name(mut index: usize, first_letter: u8) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(first_letter + (index % 26) as u8);
+        index /= 26;
+        if index == 0 {
+            break;
+        }
+    }
+    String::from_utf8(name).unwrap()
+}
+
+// A chain of `length` small caves, which has a single path for both parts, but the path is as
+// deep as the chain is long
+fn generate_chain(length: usize) -> String {
+    // The prefix keeps the names away from start and end
+    let small = |index| format!("x{}", letter_name(index, b'a'));
+    let mut connections = vec![format!("{}-{}", START, small(0))];
+    for index in 0..length.saturating_sub(1) {
+        connections.push(format!("{}-{}", small(index), small(index + 1)));
+    }
+    connections.push(format!("{}-{}", small(length.saturating_sub(1)), END));
+    connections.join("\n")
+}
+
+// A ladder of `rungs` + 1 small caves, where neighboring small caves are connected directly and
+// through a big cave, and every big cave leads to a dead end small cave
+fn generate_ladder(rungs: usize) -> String {
+    let small = |index| format!("x{}", letter_name(index, b'a'));
+    let big = |index| format!("X{}", letter_name(index, b'A'));
+    let dead_end = |index| format!("y{}", letter_name(index, b'a'));
+    let mut connections = vec![format!("{}-{}", START, small(0))];
+    for index in 0..rungs {
+        connections.push(format!("{}-{}", small(index), small(index + 1)));
+        connections.push(format!("{}-{}", small(index), big(index)));
+        connections.push(format!("{}-{}", big(index), small(index + 1)));
+        connections.push(format!("{}-{}", big(index), dead_end(index)));
+    }
+    connections.push(format!("{}-{}", small(rungs), END));
+    connections.join("\n")
+}
+
+// The memoised counter recurses once per cave of a path, so it is only used to cross-check the
+// iterative one on graphs where that cannot overflow the stack
+const MAX_CROSS_CHECKED_CAVES: usize = 1000;
+
+// The iterative counter walks every path, and a ladder has more than 3^rungs of them
+const MAX_LADDER_RUNGS: usize = 10;
+
+// Both counters have to agree
+fn check_generated_graph(description: &str, graph: &CaveGraph) {
+    for (part, budget) in [(1, PART1_BUDGET), (2, PART2_BUDGET)] {
+        let iterative = count_paths_iterative(graph, budget);
+        if graph.caves.len() <= MAX_CROSS_CHECKED_CAVES {
+            assert_eq!(iterative, count_paths(graph, budget));
+        }
+        println!("Part{} paths in {}: {}", part, description, iterative);
+    }
 }
 
 fn main() {
-    let file_content = fs::read_to_string("2021/12/input.txt").expect("Cannot read input file");
-    let mut caves = HashMap::new();
-    let mut counter: usize = 0;
-    let mut get_cave_id = |cave| {
-        if caves.contains_key(cave) {
-            *caves.get(cave).unwrap()
-        } else {
-            counter += 1;
-            caves.insert(cave, counter);
-            counter
-        }
-    };
-
-    let cave_infos: HashMap<usize, (bool, Vec<usize>, &str)> = file_content
-        .lines()
-        .map(|line| {
-            let v = line.split('-').collect::<Vec<&str>>();
-            (
-                is_full_uppercase(v[0]),
-                v[0],
-                get_cave_id(v[0]),
-                is_full_uppercase(v[1]),
-                v[1],
-                get_cave_id(v[1]),
-            )
-        })
-        .fold(
-            HashMap::new(),
-            |mut cave_infos, (is_src_big, src_name, src, is_dst_big, dst_name, dst)| {
-                let mut update_cave_info = |src, is_big, name, dst| {
-                    let mut entry = cave_infos.entry(src).or_default();
-                    entry.0 = is_big;
-                    entry.1.push(dst);
-                    entry.2 = name;
+    let mut args = env::args().skip(1).peekable();
+    let mut print_paths = None;
+    let mut custom_budget = None;
+    let mut dot_path = None;
+    let mut chain_length = None;
+    let mut ladder_rungs = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // --paths [1|2] lists the paths of the given part, part 1 by default
+            "--paths" => {
+                let budget = match args.peek().map(String::as_str) {
+                    Some("2") => PART2_BUDGET,
+                    _ => PART1_BUDGET,
+                };
+                args.next_if(|part| part == "1" || part == "2");
+                print_paths = Some(budget);
+            }
+            "--generate-chain" => {
+                chain_length = Some(
+                    args.next()
+                        .and_then(|length| length.parse::<usize>().ok())
+                        .expect("Invalid chain length"),
+                )
+            }
+            "--generate-ladder" => {
+                ladder_rungs = Some(
+                    args.next()
+                        .and_then(|rungs| rungs.parse::<usize>().ok())
+                        .filter(|&rungs| rungs <= MAX_LADDER_RUNGS)
+                        .expect("Invalid rung count, the ladder can have at most 10 rungs"),
+                )
+            }
+            "--dot" => dot_path = Some(args.next().expect("Missing DOT path")),
+            // --revisits <caves> <times>
+            "--revisits" => {
+                let mut next_number = || {
+                    args.next()
+                        .and_then(|value| value.parse::<usize>().ok())
+                        .expect("Invalid revisit budget")
                 };
+                custom_budget = Some(RevisitBudget {
+                    caves: next_number(),
+                    times: next_number(),
+                });
+            }
+            _ => panic!("Unknown argument '{}'", arg),
+        }
+    }
+
+    let file_content = fs::read_to_string("2021/12/input.txt").expect("Cannot read input file");
+    let graph = CaveGraph::parse(&file_content).expect("Invalid input");
+
+    if let Some(path) = dot_path {
+        fs::write(path, graph.to_dot()).expect("Cannot write DOT file");
+    }
+    if let Some(budget) = print_paths {
+        graph
+            .paths(budget)
+            .for_each(|path| println!("{}", graph.format_path(&path)));
+    }
+    let part1_result = part1(&graph);
+    let part2_result = part2(&graph);
+    if graph.caves.len() <= MAX_CROSS_CHECKED_CAVES {
+        assert_eq!(part1_result, part1_memoised(&graph));
+        assert_eq!(part2_result, part2_memoised(&graph));
+    }
+    print_solution(1, part1_result);
+    print_solution(2, part2_result);
+    if let Some(budget) = custom_budget {
+        println!(
+            "Paths revisiting at most {} small caves at most {} times: {}",
+            budget.caves,
+            budget.times,
+            count_paths(&graph, budget)
+        );
+    }
+    if let Some(length) = chain_length {
+        let graph = CaveGraph::parse(&generate_chain(length)).expect("Invalid generated graph");
+        check_generated_graph(
+            &format!("a generated chain of {} small caves", length),
+            &graph,
+        );
+    }
+    if let Some(rungs) = ladder_rungs {
+        let graph = CaveGraph::parse(&generate_ladder(rungs)).expect("Invalid generated graph");
+        check_generated_graph(&format!("a generated ladder of {} rungs", rungs), &graph);
+    }
+}
 
-                update_cave_info(src, is_src_big, src_name, dst);
-                update_cave_info(dst, is_dst_big, dst_name, src);
-                cave_infos
-            },
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL_EXAMPLE: &str = "start-A\nstart-b\nA-c\nA-b\nb-d\nA-end\nb-end";
+
+    #[test]
+    fn small_example_paths() {
+        let graph = CaveGraph::parse(SMALL_EXAMPLE).unwrap();
+        let mut paths = graph
+            .paths(PART1_BUDGET)
+            .map(|path| graph.format_path(&path))
+            .collect::<Vec<String>>();
+        paths.sort_unstable();
+        assert_eq!(
+            paths,
+            [
+                "start,A,b,A,c,A,end",
+                "start,A,b,A,end",
+                "start,A,b,end",
+                "start,A,c,A,b,A,end",
+                "start,A,c,A,b,end",
+                "start,A,c,A,end",
+                "start,A,end",
+                "start,b,A,c,A,end",
+                "start,b,A,end",
+                "start,b,end",
+            ]
+        );
+        assert_eq!(graph.paths(PART2_BUDGET).count(), 36);
+    }
+
+    #[test]
+    fn memoised_count_with_more_than_64_small_caves() {
+        // A ladder of 3 rungs followed by a chain of 70 small caves
+        let chain = (0..70)
+            .map(|index| format!("c{}", letter_name(index, b'a')))
+            .collect::<Vec<String>>();
+        let mut input = generate_ladder(3).replace("xd-end", &format!("xd-{}", chain[0]));
+        for pair in chain.windows(2) {
+            input.push_str(&format!("\n{}-{}", pair[0], pair[1]));
+        }
+        input.push_str(&format!("\n{}-{}", chain[69], END));
+        let graph = CaveGraph::parse(&input).unwrap();
+        assert!(
+            graph
+                .caves
+                .iter()
+                .filter(|cave| cave.size == CaveSize::Small)
+                .count()
+                > 64
         );
 
-    let start = *caves.get("start").unwrap();
-    let end = *caves.get("end").unwrap();
-    print_solution(1, part1(start, end, &cave_infos));
-    print_solution(1, part1_unwinded(start, end, &cave_infos));
-    print_solution(2, part2(start, end, &cave_infos));
+        assert_eq!(count_paths(&graph, PART1_BUDGET), 27);
+        for budget in [PART1_BUDGET, PART2_BUDGET] {
+            assert_eq!(
+                count_paths(&graph, budget),
+                count_paths_iterative(&graph, budget)
+            );
+        }
+    }
 }
@@ -1,72 +1,148 @@
-use std::{collections::HashSet, fs};
+use std::{collections::HashSet, env, fs};
 
-use common::coords::Coords;
-use common::utility::{distance, print_solution};
+use common::coords::{Coords, CoordsImpl};
+use common::ocr;
+use common::utility::print_solution;
 
-trait FoldableCoordsImpl {
-    fn fold(&self, line: &Self) -> Self;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    X,
+    Y,
 }
 
-impl<T: Eq, C: common::coords::CoordsImpl<T = T>> FoldableCoordsImpl for C {
-    fn fold(&self, line: &Self) -> Self {
-        assert!(line.x() == 0 || line.y() == 0);
+// Folds along the line `axis = at`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fold {
+    axis: Axis,
+    at: usize,
+}
 
-        let fold_coord = |coord, fold| {
-            if fold == 0 {
-                coord
-            } else {
-                fold - distance(coord, fold)
-            }
+impl Fold {
+    // Parses "fold along x=5"
+    fn parse(line: &str) -> Result<Self, String> {
+        let (axis, at) = line
+            .strip_prefix("fold along ")
+            .and_then(|fold| fold.split_once('='))
+            .ok_or_else(|| format!("Invalid fold '{}'", line))?;
+        let axis = match axis {
+            "x" => Axis::X,
+            "y" => Axis::Y,
+            _ => return Err(format!("Invalid fold axis '{}'", axis)),
         };
-        Self::new(
-            fold_coord(self.x(), line.x()),
-            fold_coord(self.y(), line.y()),
-        )
+        let at = at
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid fold position '{}'", at))?;
+        Ok(Self { axis, at })
     }
 }
 
-fn print_activation_code(height: usize, width: usize, basins: &HashSet<Coords>) -> String {
-    let mut solution = String::new();
-    solution.push('\n');
-    for row in 0..height {
-        for col in 0..width {
-            let char = if basins.contains(&[row, col]) {
-                'X'
-            } else {
-                ' '
-            };
-            solution.push(char);
+#[derive(Debug, Clone)]
+struct Paper {
+    dots: HashSet<Coords>,
+    width: usize,
+    height: usize,
+}
+
+impl Paper {
+    // The first fold along an axis halves the paper, so it gives the size along that axis unless
+    // a dot lies further away
+    fn new(dots: HashSet<Coords>, folds: &[Fold]) -> Self {
+        let size = |axis: Axis, coord: fn(&Coords) -> usize| {
+            let folded_size = folds
+                .iter()
+                .find(|fold| fold.axis == axis)
+                .map_or(0, |fold| 2 * fold.at + 1);
+            let dots_size = dots.iter().map(|c| coord(c) + 1).max().unwrap_or(0);
+            folded_size.max(dots_size)
+        };
+        let width = size(Axis::X, |c| c.x());
+        let height = size(Axis::Y, |c| c.y());
+        Self {
+            dots,
+            width,
+            height,
+        }
+    }
+
+    // The folded paper is as large as the larger side. When the far side is larger, it sticks out
+    // past the near edge, so every coordinate is shifted to keep them non-negative.
+    fn fold(&self, fold: Fold) -> Result<Self, String> {
+        let size = match fold.axis {
+            Axis::X => self.width,
+            Axis::Y => self.height,
+        };
+        if fold.at >= size {
+            return Err(format!("Fold {:?} is outside of the paper", fold));
         }
-        if row < height - 1 {
-            solution.push('\n');
+        let folded_size = fold.at.max(size - fold.at - 1);
+        let fold_coord = |coord: usize| {
+            if coord < fold.at {
+                Ok(coord + folded_size - fold.at)
+            } else if coord > fold.at {
+                Ok(folded_size + fold.at - coord)
+            } else {
+                Err(format!("Dot on the fold line {:?}", fold))
+            }
+        };
+
+        let dots = self
+            .dots
+            .iter()
+            .map(|dot| match fold.axis {
+                Axis::X => Ok([fold_coord(dot.x())?, dot.y()]),
+                Axis::Y => Ok([dot.x(), fold_coord(dot.y())?]),
+            })
+            .collect::<Result<HashSet<Coords>, String>>()?;
+        let (width, height) = match fold.axis {
+            Axis::X => (folded_size, self.height),
+            Axis::Y => (self.width, folded_size),
+        };
+        Ok(Self {
+            dots,
+            width,
+            height,
+        })
+    }
+
+    // The paper after every fold
+    fn replay(&self, folds: &[Fold]) -> Result<Vec<Paper>, String> {
+        let mut papers: Vec<Paper> = Vec::with_capacity(folds.len());
+        for (index, &fold) in folds.iter().enumerate() {
+            let paper = papers.last().unwrap_or(self);
+            let folded = paper
+                .fold(fold)
+                .map_err(|error| format!("Fold {}: {}", index + 1, error))?;
+            papers.push(folded);
         }
+        Ok(papers)
     }
-    solution
 }
 
-fn part1(dots: &HashSet<Coords>, folds: &Vec<Coords>) -> usize {
-    let fold_to_use = folds[0];
-    dots.iter()
-        .map(|c| c.fold(&fold_to_use))
-        .collect::<HashSet<Coords>>()
-        .len()
+fn part1(papers: &[Paper]) -> usize {
+    papers[0].dots.len()
 }
 
-fn part2(dots: &HashSet<Coords>, folds: &Vec<Coords>) -> String {
-    let activation_code = folds
-        .iter()
-        .fold(dots.clone(), |dots, f| {
-            dots.iter().map(|c| c.fold(f)).collect::<HashSet<Coords>>()
-        })
-        .iter()
-        .map(|c| [c[1], c[0]])
-        .collect::<HashSet<Coords>>();
-    let width = activation_code.iter().map(|c| c[1]).max().unwrap();
-    let height = activation_code.iter().map(|c| c[0]).max().unwrap();
-    print_activation_code(height + 1, width + 1, &activation_code)
+// The activation code is made of 8 capital letters
+const CODE_LENGTH: usize = 8;
+
+fn part2(papers: &[Paper]) -> String {
+    let activation_code = &papers[papers.len() - 1].dots;
+    let code = ocr::recognize(activation_code).unwrap_or_else(|image| {
+        eprintln!("{}", image);
+        panic!("Cannot recognize the activation code");
+    });
+    assert_eq!(
+        code.len(),
+        CODE_LENGTH,
+        "Unexpected activation code '{}'",
+        code
+    );
+    code
 }
 
 fn main() {
+    let print_replay = env::args().any(|arg| arg == "--replay");
+
     let file_content = fs::read_to_string("2021/13/input.txt").expect("Cannot read input file");
 
     let dots: HashSet<Coords> = file_content
@@ -84,18 +160,56 @@ fn main() {
     let folds = file_content
         .lines()
         .skip_while(|l| !l.starts_with("fold along "))
-        .map(|l| l.split(' ').collect::<Vec<&str>>()[2])
-        .map(|fold| {
-            let values = fold.split('=').collect::<Vec<&str>>();
-            let coord = values[1].parse::<usize>().expect("Unexpected input");
-            if values[0] == "x" {
-                [coord, 0]
-            } else {
-                [0, coord]
-            }
-        })
-        .collect::<Vec<Coords>>();
+        .map(Fold::parse)
+        .collect::<Result<Vec<Fold>, String>>()
+        .expect("Invalid fold");
+    assert!(!folds.is_empty(), "No folds");
+
+    let papers = Paper::new(dots, &folds)
+        .replay(&folds)
+        .expect("Invalid fold");
+    if print_replay {
+        for (fold, paper) in folds.iter().zip(papers.iter()) {
+            println!(
+                "{:?}: {}x{}, {} dots",
+                fold,
+                paper.width,
+                paper.height,
+                paper.dots.len()
+            );
+        }
+    }
+
+    print_solution(1, part1(&papers));
+    print_solution(2, part2(&papers));
+}
 
-    print_solution(1, part1(&dots, &folds));
-    print_solution(2, part2(&dots, &folds));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fold_without_dots_beyond_the_line() {
+        let dots = HashSet::from([[0, 0], [1, 0]]);
+        let folds = [Fold::parse("fold along x=5").unwrap()];
+        let papers = Paper::new(dots.clone(), &folds).replay(&folds).unwrap();
+        assert_eq!((papers[0].width, papers[0].height), (5, 1));
+        assert_eq!(papers[0].dots, dots);
+    }
+
+    #[test]
+    fn fold_with_larger_far_side() {
+        let dots = HashSet::from([[0, 0], [4, 0]]);
+        let folds = [Fold::parse("fold along x=1").unwrap()];
+        let papers = Paper::new(dots, &folds).replay(&folds).unwrap();
+        assert_eq!(papers[0].width, 3);
+        assert_eq!(papers[0].dots, HashSet::from([[2, 0], [0, 0]]));
+    }
+
+    #[test]
+    fn dot_on_the_fold_line() {
+        let dots = HashSet::from([[0, 0], [0, 3]]);
+        let folds = [Fold::parse("fold along y=3").unwrap()];
+        assert!(Paper::new(dots, &folds).replay(&folds).is_err());
+    }
 }
@@ -1,188 +1,601 @@
-use std::{collections::HashMap, fs};
+use std::{collections::HashMap, env, fs, iter, str::FromStr};
 
 use common::utility::print_solution;
 
 const ARROW: &'static str = " -> ";
 
-fn replace_one_step(formula: &str, insertion_pairs: &HashMap<(char, char), char>) -> String {
-    let mut result = vec!['_'; formula.len() * 2];
-    result[0] = formula.chars().nth(0).expect("Unexpected formula");
-    let mut counter = 1;
-    formula
-        .chars()
-        .zip(formula.chars().skip(1))
-        .for_each(|(first, second)| {
-            if let Some(replacement) = insertion_pairs.get(&(first, second)) {
-                result[counter] = *replacement;
-                counter += 1;
-            }
-            result[counter] = second;
-            counter += 1;
-        });
-    result
-        .into_iter()
-        .take_while(|c| *c != '_')
-        .collect::<String>()
-}
-
 const PART1_STEPS: usize = 10;
 const PART2_STEPS: usize = 40;
 
-// Naive method
-fn part1(formula_str: &str, insertion_pairs: &HashMap<(char, char), char>) -> usize {
-    let mut formula = formula_str.to_string();
-    for _i in 0..PART1_STEPS {
-        formula = replace_one_step(&formula, &insertion_pairs);
+type Pair = (char, char);
+
+// Tracks the number of every pair of adjacent elements and of every element, the polymer itself
+// is never built
+#[derive(Debug, Clone)]
+struct Polymer<'a> {
+    insertion_pairs: &'a HashMap<Pair, char>,
+    pairs: HashMap<Pair, u128>,
+    elements: HashMap<char, u128>,
+    length: u128,
+    last: Option<char>,
+    steps: usize,
+}
+
+impl<'a> Polymer<'a> {
+    fn new(formula_str: &str, insertion_pairs: &'a HashMap<Pair, char>) -> Self {
+        let mut polymer = Self {
+            insertion_pairs,
+            pairs: HashMap::new(),
+            elements: HashMap::new(),
+            length: formula_str.chars().count() as u128,
+            last: formula_str.chars().last(),
+            steps: 0,
+        };
+        formula_str
+            .chars()
+            .zip(formula_str.chars().skip(1))
+            .for_each(|pair| *polymer.pairs.entry(pair).or_default() += 1);
+        formula_str
+            .chars()
+            .for_each(|c| *polymer.elements.entry(c).or_default() += 1);
+        polymer
     }
 
-    let counters: HashMap<char, usize> = formula.chars().fold(HashMap::new(), |mut counters, c| {
-        *counters.entry(c).or_default() += 1;
-        counters
-    });
+    // None if the length does not fit in a u128, every count is bounded by the length
+    fn step(&self) -> Option<Self> {
+        let mut next = Self {
+            insertion_pairs: self.insertion_pairs,
+            pairs: HashMap::new(),
+            elements: self.elements.clone(),
+            length: self.length,
+            last: self.last,
+            steps: self.steps + 1,
+        };
+        for (&(a, b), &count) in &self.pairs {
+            match self.insertion_pairs.get(&(a, b)) {
+                Some(&new_char) => {
+                    next.length = next.length.checked_add(count)?;
+                    *next.pairs.entry((a, new_char)).or_default() += count;
+                    *next.pairs.entry((new_char, b)).or_default() += count;
+                    *next.elements.entry(new_char).or_default() += count;
+                }
+                None => *next.pairs.entry((a, b)).or_default() += count,
+            }
+        }
+        Some(next)
+    }
 
-    let min = counters.values().min().unwrap();
-    let max = counters.values().max().unwrap();
-    max - min
-}
+    // The polymer after every step, starting with the current one
+    fn snapshots(&self) -> impl Iterator<Item = Polymer<'a>> {
+        iter::successors(Some(self.clone()), |polymer| polymer.step())
+    }
 
-fn count_n_step(
-    first: char,
-    second: char,
-    n: usize,
-    insertion_pairs: &HashMap<(char, char), char>,
-    counters: &mut HashMap<char, usize>,
-    cache: &HashMap<(char, char), HashMap<char, usize>>,
-) {
-    if n == 0 {
-        if let Some(cached) = cache.get(&(first, second)) {
-            cached
-                .iter()
-                .for_each(|(c, n)| *counters.entry(*c).or_default() += n);
+    fn counts_after(&self, steps: usize) -> Option<HashMap<char, u128>> {
+        self.snapshots().nth(steps).map(|polymer| polymer.elements)
+    }
+
+    // Every pair the steps can produce, sorted, and the transition matrix of one step:
+    // transition[from][to] is the number of `to` pairs produced by one `from` pair
+    fn transition(&self) -> (Vec<Pair>, Matrix) {
+        let mut elements = self.elements.keys().copied().collect::<Vec<char>>();
+        elements.extend(self.insertion_pairs.values().copied());
+        elements.extend(self.insertion_pairs.keys().flat_map(|&(a, b)| [a, b]));
+        elements.sort_unstable();
+        elements.dedup();
+        let pairs = elements
+            .iter()
+            .flat_map(|&a| elements.iter().map(move |&b| (a, b)))
+            .collect::<Vec<Pair>>();
+        let index = |pair: Pair| pairs.binary_search(&pair).unwrap();
+
+        let mut transition = vec![vec![0u128; pairs.len()]; pairs.len()];
+        for (from, &(a, b)) in pairs.iter().enumerate() {
+            match self.insertion_pairs.get(&(a, b)) {
+                Some(&new_char) => {
+                    transition[from][index((a, new_char))] += 1;
+                    transition[from][index((new_char, b))] += 1;
+                }
+                None => transition[from][from] += 1,
+            }
         }
-        return;
+        (pairs, transition)
     }
 
-    if let Some(new_char) = insertion_pairs.get(&(first, second)) {
-        *counters.entry(*new_char).or_default() += 1;
-        count_n_step(first, *new_char, n - 1, insertion_pairs, counters, cache);
-        count_n_step(*new_char, second, n - 1, insertion_pairs, counters, cache);
+    // One step is a linear map on the pair counts, so `steps` steps are its power computed by
+    // repeated squaring. Every element but the last one of the polymer is the first element of
+    // exactly one pair, which gives the element counts. None if a count does not fit in a u128:
+    // when every pair has a rule the length doubles at each step, so this happens after about
+    // 128 - log2(length) steps, 127 for the 4 elements of the example.
+    fn fast_forward(&self, steps: u64) -> Option<HashMap<char, u128>> {
+        let counts = self.fast_forward_with(steps, &Arithmetic::Saturating)?;
+        if counts.values().any(|&count| count == u128::MAX) {
+            return None;
+        }
+        Some(counts)
+    }
+
+    // The element counts after `steps` steps modulo `modulus`, for step counts whose counts do
+    // not fit in a u128
+    fn fast_forward_modulo(&self, steps: u64, modulus: u64) -> Option<HashMap<char, u64>> {
+        let counts = self.fast_forward_with(steps, &Arithmetic::Modulo(u128::from(modulus)))?;
+        Some(
+            counts
+                .into_iter()
+                .map(|(element, count)| (element, count as u64))
+                .collect(),
+        )
+    }
+
+    // Zero counts are left out, so with a modulus an element whose count is a multiple of it is
+    // missing
+    fn fast_forward_with(
+        &self,
+        steps: u64,
+        arithmetic: &Arithmetic,
+    ) -> Option<HashMap<char, u128>> {
+        let last = self.last?;
+        let (pairs, transition) = self.transition();
+        let index = |pair: Pair| pairs.binary_search(&pair).unwrap();
+        let transition = power(&transition, steps, arithmetic);
+
+        let mut counts = HashMap::from([(last, arithmetic.reduce(1))]);
+        for (&pair, &count) in &self.pairs {
+            let count = arithmetic.reduce(count);
+            for (to, &(a, _b)) in pairs.iter().enumerate() {
+                let term = arithmetic.mul(count, transition[index(pair)][to]);
+                let element = counts.entry(a).or_default();
+                *element = arithmetic.add(*element, term);
+            }
+        }
+        counts.retain(|_element, count| *count != 0);
+        Some(counts)
     }
 }
 
-//  I have the POOOOOOWEEEEEEEER!
-fn part2(formula_str: &str, insertion_pairs: &HashMap<(char, char), char>) -> usize {
-    let mut cache = HashMap::<(char, char), HashMap<char, usize>>::new();
-    let empty_cache = HashMap::<(char, char), HashMap<char, usize>>::new();
-    let steps = 20;
-    for &(a, b) in insertion_pairs.keys() {
-        let mut counters = HashMap::<char, usize>::new();
-        count_n_step(a, b, steps, &insertion_pairs, &mut counters, &empty_cache);
-        cache.insert((a, b), counters);
+// Fails on malformed and duplicate rules
+fn parse_rules<'a, I: Iterator<Item = &'a str>>(lines: I) -> Result<HashMap<Pair, char>, String> {
+    let mut insertion_pairs = HashMap::new();
+    let mut definitions = HashMap::<Pair, usize /*line*/>::new();
+    // The rules start on the third line
+    for (line_number, line) in lines.enumerate().map(|(index, line)| (index + 3, line)) {
+        let (pair, insertion) = line
+            .split_once(ARROW)
+            .ok_or_else(|| format!("Line {}: invalid rule '{}'", line_number, line))?;
+        let pair = match pair.chars().collect::<Vec<char>>()[..] {
+            [a, b] => (a, b),
+            _ => return Err(format!("Line {}: invalid pair '{}'", line_number, pair)),
+        };
+        let insertion = match insertion.chars().collect::<Vec<char>>()[..] {
+            [c] => c,
+            _ => {
+                return Err(format!(
+                    "Line {}: invalid insertion '{}'",
+                    line_number, insertion
+                ))
+            }
+        };
+        if let Some(first_definition) = definitions.insert(pair, line_number) {
+            return Err(format!(
+                "Line {}: duplicate rule for '{}{}', already defined on line {}",
+                line_number, pair.0, pair.1, first_definition
+            ));
+        }
+        insertion_pairs.insert(pair, insertion);
     }
+    Ok(insertion_pairs)
+}
 
-    let mut counters = HashMap::<char, usize>::new();
-    formula_str
+// Every element that can appear in the polymer, sorted
+fn alphabet(formula_str: &str, insertion_pairs: &HashMap<Pair, char>) -> Vec<char> {
+    let mut elements = formula_str
         .chars()
-        .for_each(|c| *counters.entry(c).or_default() += 1);
-    formula_str
-        .chars()
-        .zip(formula_str.chars().skip(1))
-        .for_each(|(a, b)| count_n_step(a, b, steps, &insertion_pairs, &mut counters, &cache));
-
-    let min = counters.values().min().unwrap();
-    let max = counters.values().max().unwrap();
-    max - min
+        .chain(insertion_pairs.values().copied())
+        .collect::<Vec<char>>();
+    elements.sort_unstable();
+    elements.dedup();
+    elements
 }
 
-fn step_one(
-    pairs: &HashMap<(char, char), usize>,
-    insertion_pairs: &HashMap<(char, char), char>,
-) -> HashMap<(char, char), usize> {
-    pairs
+// Pairs of the alphabet without an insertion rule, they are left as they are by every step
+fn uncovered_pairs(formula_str: &str, insertion_pairs: &HashMap<Pair, char>) -> Vec<Pair> {
+    let elements = alphabet(formula_str, insertion_pairs);
+    elements
         .iter()
-        .fold(HashMap::new(), |mut acc, (&(a, b), &count)| {
-            if let Some(&new_char) = insertion_pairs.get(&(a, b)) {
-                *acc.entry((a, new_char)).or_default() += count;
-                *acc.entry((new_char, b)).or_default() += count;
+        .flat_map(|&a| elements.iter().map(move |&b| (a, b)))
+        .filter(|pair| !insertion_pairs.contains_key(pair))
+        .collect()
+}
+
+type Matrix = Vec<Vec<u128>>;
+
+enum Arithmetic {
+    // Saturates instead of overflowing, a saturated entry only reaches the result when the exact
+    // result does not fit either
+    Saturating,
+    // Modulo a value that fits in a u64, so that the product of two reduced values fits in a u128
+    Modulo(u128),
+}
+
+impl Arithmetic {
+    fn reduce(&self, value: u128) -> u128 {
+        match self {
+            Self::Saturating => value,
+            Self::Modulo(modulus) => value % modulus,
+        }
+    }
+
+    fn add(&self, lhs: u128, rhs: u128) -> u128 {
+        match self {
+            Self::Saturating => lhs.saturating_add(rhs),
+            Self::Modulo(modulus) => (lhs + rhs) % modulus,
+        }
+    }
+
+    fn mul(&self, lhs: u128, rhs: u128) -> u128 {
+        match self {
+            Self::Saturating => lhs.saturating_mul(rhs),
+            Self::Modulo(modulus) => lhs * rhs % modulus,
+        }
+    }
+}
+
+fn multiply(lhs: &Matrix, rhs: &Matrix, arithmetic: &Arithmetic) -> Matrix {
+    let size = lhs.len();
+    let mut product = vec![vec![0u128; size]; size];
+    for row in 0..size {
+        for k in (0..size).filter(|&k| lhs[row][k] != 0) {
+            for col in 0..size {
+                let term = arithmetic.mul(lhs[row][k], rhs[k][col]);
+                product[row][col] = arithmetic.add(product[row][col], term);
             }
-            acc
+        }
+    }
+    product
+}
+
+fn identity(size: usize, arithmetic: &Arithmetic) -> Matrix {
+    (0..size)
+        .map(|row| {
+            (0..size)
+                .map(|col| arithmetic.reduce(u128::from(row == col)))
+                .collect()
         })
+        .collect()
 }
 
-// After opening the subreddit and seeing the lanternfish PTSD posts, it popped...
-fn count_n_step_efficient(
-    formula_str: &str,
-    insertion_pairs: &HashMap<(char, char), char>,
+fn power(matrix: &Matrix, mut exponent: u64, arithmetic: &Arithmetic) -> Matrix {
+    let mut result = identity(matrix.len(), arithmetic);
+    let mut base = matrix
+        .iter()
+        .map(|row| row.iter().map(|&entry| arithmetic.reduce(entry)).collect())
+        .collect::<Matrix>();
+    while exponent > 0 {
+        if exponent % 2 == 1 {
+            result = multiply(&result, &base, arithmetic);
+        }
+        exponent /= 2;
+        if exponent > 0 {
+            base = multiply(&base, &base, arithmetic);
+        }
+    }
+    result
+}
+
+// The polymer after a given number of steps, built lazily. The expansion of a pair is what its
+// first element turns into after the steps without that element: every element inserted between
+// them followed by the second element.
+struct Expansion<'a> {
+    formula_str: &'a str,
+    insertion_pairs: &'a HashMap<Pair, char>,
     steps: usize,
-) -> usize {
-    let mut pairs: HashMap<(char, char), usize> = formula_str
-        .chars()
-        .zip(formula_str.chars().skip(1))
-        .fold(HashMap::new(), |mut acc, (a, b)| {
-            *acc.entry((a, b)).or_default() += 1;
-            acc
-        });
-
-    for _i in 0..steps {
-        pairs = step_one(&pairs, insertion_pairs);
-    }
-
-    let mut counters = pairs.iter().fold(
-        HashMap::<char, usize>::new(),
-        |mut acc, (&(a, b), &count)| {
-            *acc.entry(a).or_default() += count;
-            *acc.entry(b).or_default() += count;
-            acc
-        },
-    );
+    // lengths[n][pair] is the length of the expansion of the pair after n steps, saturated
+    lengths: Vec<HashMap<Pair, u128>>,
+}
+
+impl<'a> Expansion<'a> {
+    fn new(formula_str: &'a str, insertion_pairs: &'a HashMap<Pair, char>, steps: usize) -> Self {
+        let elements = alphabet(formula_str, insertion_pairs);
+        let pairs = elements
+            .iter()
+            .flat_map(|&a| elements.iter().map(move |&b| (a, b)))
+            .collect::<Vec<Pair>>();
+        let mut lengths = vec![pairs
+            .iter()
+            .map(|&pair| (pair, 1))
+            .collect::<HashMap<Pair, u128>>()];
+        for n in 1..=steps {
+            let previous = &lengths[n - 1];
+            let next = pairs
+                .iter()
+                .map(|&(a, b)| match insertion_pairs.get(&(a, b)) {
+                    Some(&c) => ((a, b), previous[&(a, c)].saturating_add(previous[&(c, b)])),
+                    None => ((a, b), previous[&(a, b)]),
+                })
+                .collect();
+            lengths.push(next);
+        }
+        Self {
+            formula_str,
+            insertion_pairs,
+            steps,
+            lengths,
+        }
+    }
+
+    fn pairs(&self) -> impl Iterator<Item = Pair> + 'a {
+        self.formula_str
+            .chars()
+            .zip(self.formula_str.chars().skip(1))
+    }
+
+    // Saturated
+    fn len(&self) -> u128 {
+        self.pairs().fold(
+            self.formula_str.chars().count().min(1) as u128,
+            |acc, pair| acc.saturating_add(self.lengths[self.steps][&pair]),
+        )
+    }
+
+    fn chars(&self) -> PolymerChars<'a> {
+        let mut stack = self
+            .pairs()
+            .map(|pair| (pair, self.steps))
+            .collect::<Vec<(Pair, usize)>>();
+        stack.reverse();
+        PolymerChars {
+            first: self.formula_str.chars().next(),
+            stack,
+            insertion_pairs: self.insertion_pairs,
+        }
+    }
 
-    *counters
-        .entry(formula_str.chars().next().unwrap())
-        .or_default() += 1;
-    *counters
-        .entry(formula_str.chars().rev().next().unwrap())
-        .or_default() += 1;
-    let min = counters.values().min().unwrap();
-    let max = counters.values().max().unwrap();
-    (max - min) / 2
+    // Descends into the only pair expansion containing the index at every step
+    fn char_at(&self, mut index: u128) -> Option<char> {
+        if index == 0 {
+            return self.formula_str.chars().next();
+        }
+        index -= 1;
+        let mut pair = self.pairs().find(|pair| {
+            let length = self.lengths[self.steps][pair];
+            if index < length {
+                true
+            } else {
+                index -= length;
+                false
+            }
+        })?;
+        let mut n = self.steps;
+        loop {
+            let (a, b) = pair;
+            match self.insertion_pairs.get(&pair) {
+                Some(&c) if n > 0 => {
+                    let left_length = self.lengths[n - 1][&(a, c)];
+                    if index < left_length {
+                        pair = (a, c);
+                    } else {
+                        index -= left_length;
+                        pair = (c, b);
+                    }
+                    n -= 1;
+                }
+                _ => return Some(b),
+            }
+        }
+    }
 }
 
-fn part1_efficient(formula_str: &str, insertion_pairs: &HashMap<(char, char), char>) -> usize {
-    count_n_step_efficient(formula_str, insertion_pairs, PART1_STEPS)
+struct PolymerChars<'a> {
+    first: Option<char>,
+    // Pairs to expand with their remaining steps, the next one on top
+    stack: Vec<(Pair, usize)>,
+    insertion_pairs: &'a HashMap<Pair, char>,
 }
 
-fn part2_efficient(formula_str: &str, insertion_pairs: &HashMap<(char, char), char>) -> usize {
-    count_n_step_efficient(formula_str, insertion_pairs, PART2_STEPS)
+impl<'a> Iterator for PolymerChars<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if let Some(first) = self.first.take() {
+            return Some(first);
+        }
+        loop {
+            let ((a, b), n) = self.stack.pop()?;
+            match self.insertion_pairs.get(&(a, b)) {
+                Some(&c) if n > 0 => {
+                    self.stack.push(((c, b), n - 1));
+                    self.stack.push(((a, c), n - 1));
+                }
+                _ => return Some(b),
+            }
+        }
+    }
 }
 
+fn most_minus_least_common(counts: &HashMap<char, u128>) -> u128 {
+    let min = counts.values().min().unwrap();
+    let max = counts.values().max().unwrap();
+    max - min
+}
+
+fn part1(formula_str: &str, insertion_pairs: &HashMap<(char, char), char>) -> u128 {
+    let counts = Polymer::new(formula_str, insertion_pairs)
+        .counts_after(PART1_STEPS)
+        .expect("Overflow");
+    most_minus_least_common(&counts)
+}
+
+fn part2(formula_str: &str, insertion_pairs: &HashMap<(char, char), char>) -> u128 {
+    let counts = Polymer::new(formula_str, insertion_pairs)
+        .counts_after(PART2_STEPS)
+        .expect("Overflow");
+    most_minus_least_common(&counts)
+}
+
+// Fails on a missing or invalid argument, Ok(None) without the flag
+fn parse_flag_pair<T: FromStr, U: FromStr>(flag: &str) -> Result<Option<(T, U)>, String> {
+    let mut args = env::args().skip_while(|arg| arg != flag).skip(1);
+    let first = match args.next() {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+    let second = args
+        .next()
+        .ok_or_else(|| format!("Missing second argument of {}", flag))?;
+    let first = first
+        .parse::<T>()
+        .map_err(|_| format!("Invalid argument '{}' of {}", first, flag))?;
+    let second = second
+        .parse::<U>()
+        .map_err(|_| format!("Invalid argument '{}' of {}", second, flag))?;
+    Ok(Some((first, second)))
+}
+
+const USAGE: &str = "\
+Flags:
+  --steps N             polymer length and element counts after every step up to N
+  --fast-forward N      difference after N steps, by matrix exponentiation. The counts are u128,
+                        which overflow after about 128 - log2(polymer length) steps when every
+                        pair has a rule, 127 steps for the example.
+  --modulo M            with --fast-forward, the element counts modulo M (at most 2^64 - 1)
+                        instead, for any number of steps
+  --polymer N LENGTH    first LENGTH elements of the polymer after N steps
+  --char-at N I         element at index I of the polymer after N steps
+  --help                this message
+";
+
 fn main() {
+    if env::args().any(|arg| arg == "--help") {
+        print!("{}", USAGE);
+        return;
+    }
+    // --steps N prints the polymer length and element counts after every step up to N
+    let snapshot_steps = env::args()
+        .skip_while(|arg| arg != "--steps")
+        .nth(1)
+        .map(|steps| steps.parse::<usize>().expect("Invalid step count"));
+    // --polymer N LENGTH prints the first LENGTH elements of the polymer after N steps
+    let polymer_prefix = parse_flag_pair::<usize, usize>("--polymer").expect("Invalid flag");
+    // --char-at N I prints the element at index I of the polymer after N steps
+    let char_at = parse_flag_pair::<usize, u128>("--char-at").expect("Invalid flag");
+    // --fast-forward N prints the difference after N steps computed by matrix exponentiation,
+    // with --modulo M the element counts modulo M
+    let fast_forward_steps = env::args()
+        .skip_while(|arg| arg != "--fast-forward")
+        .nth(1)
+        .map(|steps| steps.parse::<u64>().expect("Invalid step count"));
+    let modulus =
+        env::args()
+            .skip_while(|arg| arg != "--modulo")
+            .nth(1)
+            .map(|modulus| match modulus.parse::<u64>() {
+                Ok(modulus) if modulus > 0 => modulus,
+                _ => panic!("Invalid modulus '{}'", modulus),
+            });
+
     let file_content =
         fs::read_to_string("2021/14/input_small.txt").expect("Cannot read input file");
-    let insertion_pairs = file_content
-        .lines()
-        .skip(2)
-        .map(|line| {
-            let insertion_pair = line.split(ARROW).collect::<Vec<&str>>();
-            (
-                (
-                    insertion_pair[0].chars().nth(0).expect("Unexpected input"),
-                    insertion_pair[0].chars().nth(1).expect("Unexpected input"),
-                ),
-                insertion_pair[1].chars().nth(0).expect("Unexpected input"),
-            )
-        })
-        .fold(HashMap::new(), |mut insertion_pairs, (base, insertion)| {
-            insertion_pairs.insert(base, insertion);
-            insertion_pairs
-        });
+    let insertion_pairs = parse_rules(file_content.lines().skip(2)).expect("Invalid rules");
     let formula = file_content
         .lines()
         .next()
         .expect("Unexpected input")
         .trim();
 
-    print_solution(1, part1_efficient(formula, &insertion_pairs));
+    let uncovered = uncovered_pairs(formula, &insertion_pairs);
+    if !uncovered.is_empty() {
+        eprintln!(
+            "Warning: no insertion rule for {}",
+            uncovered
+                .iter()
+                .map(|(a, b)| format!("'{}{}'", a, b))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+    }
+
+    if let Some(steps) = snapshot_steps {
+        let polymer = Polymer::new(formula, &insertion_pairs);
+        let mut snapshots = polymer.snapshots().take(steps + 1).peekable();
+        while let Some(snapshot) = snapshots.next() {
+            let mut counts = snapshot.elements.iter().collect::<Vec<(&char, &u128)>>();
+            counts.sort_unstable();
+            println!(
+                "Step {}: length {}, difference {}, counts {:?}",
+                snapshot.steps,
+                snapshot.length,
+                most_minus_least_common(&snapshot.elements),
+                counts
+            );
+            if snapshots.peek().is_none() && snapshot.steps < steps {
+                println!("Step {}: counts overflow", snapshot.steps + 1);
+            }
+        }
+    }
+
+    if let Some((steps, length)) = polymer_prefix {
+        let expansion = Expansion::new(formula, &insertion_pairs, steps);
+        println!(
+            "After {} steps ({} elements): {}",
+            steps,
+            expansion.len(),
+            expansion.chars().take(length).collect::<String>()
+        );
+    }
+    if let Some((steps, index)) = char_at {
+        match Expansion::new(formula, &insertion_pairs, steps).char_at(index) {
+            Some(c) => println!("After {} steps: '{}' at index {}", steps, c, index),
+            None => println!("After {} steps: index {} is out of bounds", steps, index),
+        }
+    }
+    let expansion = Expansion::new(formula, &insertion_pairs, PART1_STEPS);
+    debug_assert!(expansion
+        .chars()
+        .enumerate()
+        .step_by(97)
+        .all(|(index, c)| expansion.char_at(index as u128) == Some(c)));
+
+    match (fast_forward_steps, modulus) {
+        (Some(steps), None) => match Polymer::new(formula, &insertion_pairs).fast_forward(steps) {
+            Some(counts) => println!(
+                "After {} steps: difference {}",
+                steps,
+                most_minus_least_common(&counts)
+            ),
+            None => println!(
+                "After {} steps: counts overflow, see --modulo in --help",
+                steps
+            ),
+        },
+        (Some(steps), Some(modulus)) => {
+            let polymer = Polymer::new(formula, &insertion_pairs);
+            let counts = polymer
+                .fast_forward_modulo(steps, modulus)
+                .unwrap_or_default();
+            let mut counts = alphabet(formula, &insertion_pairs)
+                .into_iter()
+                .map(|element| (element, counts.get(&element).copied().unwrap_or(0)))
+                .collect::<Vec<(char, u64)>>();
+            counts.sort_unstable();
+            println!(
+                "After {} steps: counts modulo {} {:?}",
+                steps, modulus, counts
+            );
+        }
+        (None, Some(_)) => panic!("--modulo needs --fast-forward"),
+        (None, None) => (),
+    }
+
     print_solution(1, part1(formula, &insertion_pairs));
-    print_solution(2, part2_efficient(formula, &insertion_pairs));
-    print_solution(2, part2(formula, &insertion_pairs));
+    let part2_result = part2(formula, &insertion_pairs);
+    let counts = Polymer::new(formula, &insertion_pairs)
+        .fast_forward(PART2_STEPS as u64)
+        .expect("Overflow");
+    assert_eq!(most_minus_least_common(&counts), part2_result);
+    let modulus = 1_000_000_007;
+    debug_assert!(Polymer::new(formula, &insertion_pairs)
+        .fast_forward_modulo(PART2_STEPS as u64, modulus)
+        .unwrap_or_default()
+        .iter()
+        .all(|(element, &count)| u128::from(count) == counts[element] % u128::from(modulus)));
+    print_solution(2, part2_result);
 }
@@ -1,201 +1,127 @@
 use std::{
-    collections::{HashMap, HashSet},
-    fmt::Display,
+    collections::HashMap,
+    env,
+    fmt::{self, Display},
     fs,
-    iter::Zip,
 };
 
+use common::grid::Grid;
 use common::utility::print_solution;
 
-const WIDTH: usize = 5;
-const HEIGHT: usize = 5;
-const BOARD_SIZE: usize = WIDTH * HEIGHT;
-
-type Board<T> = [T; BOARD_SIZE];
+type Board<T> = Grid<T>;
 
 #[allow(dead_code)]
 fn print<T: Display>(board: &Board<T>) {
-    for row in 0..HEIGHT {
+    for row in 0..board.height() {
         print!("[");
-        let first = row * WIDTH;
-        for col in 0..WIDTH {
+        for col in 0..board.width() {
             print!(
                 "{}{}",
-                board[first + col],
-                if col == WIDTH - 1 { "" } else { "," }
+                board[(row, col)],
+                if col == board.width() - 1 { "" } else { "," }
             );
         }
         println!("]");
     }
 }
 
-struct BoardRowIterator<'a, T> {
-    board: &'a Board<T>,
-    row: usize,
-    current_index: usize,
+// Cells that win the board once all of them are marked
+type Line = Vec<(usize, usize)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WinRule {
+    Rows,
+    Columns,
+    // Only on square boards
+    Diagonals,
+    FourCorners,
+    FullCard,
 }
 
-impl<'a, T> BoardRowIterator<'a, T> {
-    fn new(board: &'a Board<T>, row: usize) -> Option<Self> {
-        if row < HEIGHT {
-            Some(Self {
-                board,
-                row,
-                current_index: 0,
-            })
-        } else {
-            None
+impl WinRule {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "rows" => Ok(Self::Rows),
+            "columns" => Ok(Self::Columns),
+            "diagonals" => Ok(Self::Diagonals),
+            "corners" => Ok(Self::FourCorners),
+            "full" => Ok(Self::FullCard),
+            _ => Err(format!("Unknown win rule '{}'", name)),
         }
     }
-}
 
-impl<'a, T> Iterator for BoardRowIterator<'a, T> {
-    type Item = &'a T;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.current_index {
-            x if x < WIDTH => {
-                let ret = Some(&self.board[self.row * WIDTH + self.current_index]);
-                self.current_index += 1;
-                ret
+    fn lines(&self, width: usize, height: usize) -> Vec<Line> {
+        match self {
+            Self::Rows => (0..height)
+                .map(|row| (0..width).map(|col| (row, col)).collect())
+                .collect(),
+            Self::Columns => (0..width)
+                .map(|col| (0..height).map(|row| (row, col)).collect())
+                .collect(),
+            Self::Diagonals if width == height => vec![
+                (0..width).map(|i| (i, i)).collect(),
+                (0..width).map(|i| (i, width - 1 - i)).collect(),
+            ],
+            Self::Diagonals => Vec::new(),
+            Self::FourCorners => {
+                let mut corners = vec![
+                    (0, 0),
+                    (0, width - 1),
+                    (height - 1, 0),
+                    (height - 1, width - 1),
+                ];
+                corners.sort_unstable();
+                corners.dedup();
+                vec![corners]
             }
-            _ => None,
+            Self::FullCard => vec![(0..height)
+                .flat_map(|row| (0..width).map(move |col| (row, col)))
+                .collect()],
         }
     }
 }
 
-struct BoardColumnIterator<'a, T> {
-    board: &'a Board<T>,
-    column: usize,
-    current_index: usize,
-}
+const STANDARD_RULES: [WinRule; 2] = [WinRule::Rows, WinRule::Columns];
 
-impl<'a, T> BoardColumnIterator<'a, T> {
-    fn new(board: &'a Board<T>, column: usize) -> Option<Self> {
-        if column < WIDTH {
-            Some(Self {
-                board,
-                column,
-                current_index: 0,
-            })
-        } else {
-            None
-        }
-    }
+// The winning lines of every board of a game
+struct Bingo {
+    lines: Vec<Line>,
+    // Indices of the lines containing every cell
+    lines_by_cell: Grid<Vec<usize>>,
 }
 
-impl<'a, T> Iterator for BoardColumnIterator<'a, T> {
-    type Item = &'a T;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.current_index {
-            x if x < HEIGHT => {
-                let ret = Some(&self.board[self.column + HEIGHT * self.current_index]);
-                self.current_index += 1;
-                ret
-            }
-            _ => None,
+impl Bingo {
+    fn new(width: usize, height: usize, rules: &[WinRule]) -> Self {
+        let mut lines = rules
+            .iter()
+            .flat_map(|rule| rule.lines(width, height))
+            .collect::<Vec<Line>>();
+        lines.dedup();
+        let mut lines_by_cell =
+            Grid::from_rows(vec![vec![Vec::new(); width]; height]).expect("Invalid board size");
+        for (index, line) in lines.iter().enumerate() {
+            line.iter().for_each(|&pos| lines_by_cell[pos].push(index));
         }
-    }
-}
-
-type IterGetter<'a, T, I> = fn(&'a Board<T>, usize) -> Option<I>;
-
-struct BoardIterator<'a, T, I> {
-    board: &'a Board<T>,
-    iterator_getter: IterGetter<'a, T, I>,
-    current_index: usize,
-}
-
-impl<'a, T, I> BoardIterator<'a, T, I> {
-    fn new(board: &'a Board<T>, iterator_getter: IterGetter<'a, T, I>) -> BoardIterator<'a, T, I> {
         Self {
-            board,
-            iterator_getter,
-            current_index: 0,
+            lines,
+            lines_by_cell,
         }
     }
-}
-
-impl<'a, T, I> Iterator for BoardIterator<'a, T, I> {
-    type Item = I;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let ret = (self.iterator_getter)(self.board, self.current_index);
-        self.current_index += 1;
-        ret
-    }
-}
-
-trait IterableBoard<'a, T: 'a> {
-    type RowIterator: Iterator<Item = &'a T>;
-    type ColumnIterator: Iterator<Item = &'a T>;
-    type RowsIterator: Iterator<Item = Self::RowIterator>;
-    type ColumnsIterator: Iterator<Item = Self::ColumnIterator>;
-
-    fn iter_row(&'a self, row_index: usize) -> Option<Self::RowIterator>;
-    fn iter_col(&'a self, row_index: usize) -> Option<Self::ColumnIterator>;
-    fn iter_rows(&'a self) -> Self::RowsIterator;
-    fn iter_cols(&'a self) -> Self::ColumnsIterator;
-
-    fn flat_index_to_row(flat_index: usize) -> usize {
-        flat_index / WIDTH
-    }
-
-    fn flat_index_to_col(flat_index: usize) -> usize {
-        flat_index % WIDTH
-    }
-
-    fn iter_row_by_flat_index(&'a self, flat_index: usize) -> Option<Self::RowIterator> {
-        self.iter_row(Self::flat_index_to_row(flat_index))
-    }
-
-    fn iter_col_by_flat_index_(&'a self, flat_index: usize) -> Option<Self::ColumnIterator> {
-        self.iter_col(Self::flat_index_to_col(flat_index))
-    }
-
-    fn iter_zipped_by_flat_index(
-        &'a self,
-        flat_index: usize,
-    ) -> Option<
-        Zip<
-            <Self as IterableBoard<'a, T>>::RowIterator,
-            <Self as IterableBoard<'a, T>>::ColumnIterator,
-        >,
-    > {
-        self.iter_row_by_flat_index(flat_index)
-            .and_then(|row_iter| {
-                self.iter_col_by_flat_index_(flat_index)
-                    .and_then(|col_iter| Some(row_iter.zip(col_iter)))
-            })
-    }
-}
-
-impl<'a, T: 'a> IterableBoard<'a, T> for Board<T> {
-    type RowIterator = BoardRowIterator<'a, T>;
-    type ColumnIterator = BoardColumnIterator<'a, T>;
-    type RowsIterator = BoardIterator<'a, T, Self::RowIterator>;
-    type ColumnsIterator = BoardIterator<'a, T, Self::ColumnIterator>;
 
-    fn iter_row(self: &'a Self, row_index: usize) -> Option<Self::RowIterator> {
-        BoardRowIterator::new(self, row_index)
-    }
-    fn iter_col(self: &'a Self, column: usize) -> Option<Self::ColumnIterator> {
-        BoardColumnIterator::new(self, column)
-    }
-    fn iter_rows(&'a self) -> Self::RowsIterator {
-        BoardIterator::new(self, Self::iter_row)
-    }
-    fn iter_cols(&'a self) -> Self::ColumnsIterator {
-        BoardIterator::new(self, Self::iter_col)
+    // A completed line containing the newly marked cell
+    fn completed_line(&self, marks: &MarkBoard, marked: (usize, usize)) -> Option<&Line> {
+        self.lines_by_cell[marked]
+            .iter()
+            .map(|&index| &self.lines[index])
+            .find(|line| line.iter().all(|&pos| marks[pos]))
     }
 }
 
 type NumberBoard = Board<usize>;
 type MarkBoard = Board<bool>;
-type Lookup = HashMap<usize, Vec<(usize /*board*/, usize /*flat_index*/)>>;
+type Lookup = HashMap<usize, Vec<(usize /*board*/, (usize, usize))>>;
 
-fn calculate_board_score(num_board: &Board<usize>, mark_board: &Board<bool>) -> usize {
+fn calculate_board_score(num_board: &NumberBoard, mark_board: &MarkBoard) -> usize {
     num_board
         .iter()
         .zip(mark_board.iter())
@@ -204,124 +130,274 @@ fn calculate_board_score(num_board: &Board<usize>, mark_board: &Board<bool>) ->
         .sum::<usize>()
 }
 
-fn build_lookup(num_boards: &Vec<Board<usize>>) -> Lookup {
+// A number appearing several times on a board maps to all of its cells, so drawing it marks
+// every occurrence
+fn build_lookup(num_boards: &[NumberBoard]) -> Lookup {
     num_boards
         .iter()
         .enumerate()
         .fold(Lookup::default(), |mut lookup, (board_index, board)| {
-            board.iter().enumerate().for_each(|(flat_index, num)| {
-                if !lookup.contains_key(num) {
-                    lookup.insert(*num, Vec::<(usize, usize)>::new());
-                }
-                lookup.get_mut(num).unwrap().push((board_index, flat_index));
+            board.positions().for_each(|pos| {
+                lookup
+                    .entry(board[pos])
+                    .or_default()
+                    .push((board_index, pos));
             });
             lookup
         })
 }
 
-fn part1(guesses: &Vec<usize>, num_boards: &Vec<NumberBoard>, lookup: &Lookup) -> usize {
-    let mut mark_boards = vec![MarkBoard::default(); num_boards.len()];
+#[derive(Debug)]
+struct Win {
+    board: usize,
+    number: usize,
+    // 1 for the first number drawn
+    turn: usize,
+    line: Line,
+    score: usize,
+}
 
-    for guess in guesses {
-        if let Some(affect_fields) = lookup.get(guess) {
-            affect_fields.iter().for_each(|&(board, flat_index)| {
-                mark_boards[board][flat_index] = true;
-            });
-            let mut winning_board = 0;
-            let completed = affect_fields.iter().any(|&(board_index, flat_index)| {
-                let board = &mark_boards[board_index];
-                let (row_completed, col_completed) = board
-                    .iter_zipped_by_flat_index(flat_index)
-                    .unwrap()
-                    .fold((true, true), |(row_res, col_res), (row, col)| {
-                        (row_res && *row, col_res && *col)
-                    });
-
-                let won = row_completed || col_completed;
-                if won {
-                    winning_board = board_index;
-                }
-                won
-            });
-            if completed {
-                let board_score =
-                    calculate_board_score(&num_boards[winning_board], &mark_boards[winning_board]);
-                return board_score * guess;
+// Every board wins at most once, boards winning with the same number are ordered by index
+fn simulate(
+    guesses: &[usize],
+    num_boards: &[NumberBoard],
+    lookup: &Lookup,
+    bingo: &Bingo,
+) -> Vec<Win> {
+    let mut mark_boards = num_boards
+        .iter()
+        .map(|board| board.map(|_| false))
+        .collect::<Vec<MarkBoard>>();
+    let mut has_won = vec![false; num_boards.len()];
+
+    let mut timeline = Vec::new();
+    for (turn, &guess) in guesses.iter().enumerate() {
+        let affect_fields = match lookup.get(&guess) {
+            Some(affect_fields) => affect_fields,
+            None => continue,
+        };
+        affect_fields.iter().for_each(|&(board, pos)| {
+            mark_boards[board][pos] = true;
+        });
+        for &(board, pos) in affect_fields {
+            if has_won[board] {
+                continue;
+            }
+            if let Some(line) = bingo.completed_line(&mark_boards[board], pos) {
+                has_won[board] = true;
+                timeline.push(Win {
+                    board,
+                    number: guess,
+                    turn: turn + 1,
+                    line: line.clone(),
+                    score: calculate_board_score(&num_boards[board], &mark_boards[board]) * guess,
+                });
             }
         }
     }
-    unreachable!()
+    timeline
 }
 
-fn part2(guesses: &Vec<usize>, num_boards: &Vec<NumberBoard>, lookup: &Lookup) -> usize {
-    let mut mark_boards = vec![MarkBoard::default(); num_boards.len()];
+enum Outcome {
+    Score(usize),
+    NoBoardWins,
+    // Only for the last winner
+    SomeBoardsNeverWin { never_winning: usize },
+}
 
-    let mut winning_boards = HashSet::<usize>::new();
-    let mut last_board_to_win = 0;
-    for guess in guesses {
-        if let Some(affect_fields) = lookup.get(guess) {
-            affect_fields.iter().for_each(|&(board, flat_index)| {
-                mark_boards[board][flat_index] = true;
-            });
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Score(score) => write!(f, "{}", score),
+            Outcome::NoBoardWins => write!(f, "unavailable, no board wins"),
+            Outcome::SomeBoardsNeverWin { never_winning } => {
+                write!(f, "unavailable, {} board(s) never win", never_winning)
+            }
+        }
+    }
+}
+
+fn part1(timeline: &[Win]) -> Outcome {
+    match timeline.first() {
+        Some(win) => Outcome::Score(win.score),
+        None => Outcome::NoBoardWins,
+    }
+}
+
+fn part2(timeline: &[Win], board_count: usize) -> Outcome {
+    match timeline.last() {
+        None => Outcome::NoBoardWins,
+        Some(win) if timeline.len() == board_count => Outcome::Score(win.score),
+        Some(_) => Outcome::SomeBoardsNeverWin {
+            never_winning: board_count - timeline.len(),
+        },
+    }
+}
+
+struct Game {
+    draws: Vec<usize>,
+    boards: Vec<NumberBoard>,
+}
 
-            affect_fields.iter().for_each(|&(board_index, flat_index)| {
-                if winning_boards.contains(&board_index) {
-                    return;
+fn parse_numbers(numbers: &str, separator: Option<char>) -> Result<Vec<usize>, String> {
+    let parse = |num_str: &str| {
+        num_str
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid number '{}'", num_str.trim()))
+    };
+    match separator {
+        Some(separator) => numbers.split(separator).map(parse).collect(),
+        None => numbers.split_whitespace().map(parse).collect(),
+    }
+}
+
+// The first line holds the draws, boards are blocks of lines separated by empty lines and their
+// size is given by their shape. Every invalid board is reported.
+fn parse_game(content: &str) -> Result<Game, Vec<String>> {
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()));
+    let draws = lines
+        .next()
+        .ok_or_else(|| vec!["Empty input".to_string()])
+        .and_then(|(_line_number, line)| {
+            parse_numbers(line, Some(',')).map_err(|error| vec![format!("Draws: {}", error)])
+        })?;
+
+    // (first line number, rows)
+    let mut blocks: Vec<(usize, Vec<&str>)> = Vec::new();
+    let mut in_block = false;
+    for (line_number, line) in lines {
+        match (line.is_empty(), in_block) {
+            (true, _) => in_block = false,
+            (false, true) => blocks.last_mut().unwrap().1.push(line),
+            (false, false) => {
+                blocks.push((line_number, vec![line]));
+                in_block = true;
+            }
+        }
+    }
+    if blocks.is_empty() {
+        return Err(vec!["No boards".to_string()]);
+    }
+
+    let mut boards = Vec::new();
+    let mut errors = Vec::new();
+    let mut size = None;
+    for (index, (first_line, rows)) in blocks.into_iter().enumerate() {
+        let board = rows
+            .iter()
+            .enumerate()
+            .map(|(row, line)| {
+                parse_numbers(line, None)
+                    .map_err(|error| format!("line {}: {}", first_line + row, error))
+            })
+            .collect::<Result<Vec<Vec<usize>>, String>>()
+            .and_then(|rows| {
+                let width = rows[0].len();
+                match rows.iter().position(|row| row.len() != width) {
+                    Some(row) => Err(format!(
+                        "line {}: {} numbers instead of {}",
+                        first_line + row,
+                        rows[row].len(),
+                        width
+                    )),
+                    None => Grid::from_rows(rows),
                 }
-                let board = &mark_boards[board_index];
-                let won = board
-                    .iter_row_by_flat_index(flat_index)
-                    .unwrap()
-                    .all(|&mark| mark)
-                    || board
-                        .iter_col_by_flat_index_(flat_index)
-                        .unwrap()
-                        .all(|&mark| mark);
-                if won {
-                    winning_boards.insert(board_index);
-                    last_board_to_win = board_index;
+            })
+            .and_then(|board| match size {
+                Some((width, height)) if (board.width(), board.height()) != (width, height) => {
+                    Err(format!(
+                        "line {}: {}x{} instead of {}x{}",
+                        first_line,
+                        board.width(),
+                        board.height(),
+                        width,
+                        height
+                    ))
+                }
+                _ => {
+                    size = Some((board.width(), board.height()));
+                    Ok(board)
                 }
             });
-        }
-        if winning_boards.len() == mark_boards.len() {
-            let board_score = calculate_board_score(
-                &num_boards[last_board_to_win],
-                &mark_boards[last_board_to_win],
-            );
-            return board_score * guess;
+        match board {
+            Ok(board) => boards.push(board),
+            Err(error) => errors.push(format!("Board {}, {}", index, error)),
         }
     }
-    unreachable!()
-}
 
-fn main() {
-    let file_content = fs::read_to_string("2021/4/input.txt").expect("Cannot read input file");
-    let guesses = file_content
-        .lines()
-        .next()
-        .unwrap()
-        .split(",")
-        .map(|num_str| num_str.parse::<usize>().expect("Invalid number"))
-        .collect::<Vec<usize>>();
+    if errors.is_empty() {
+        Ok(Game { draws, boards })
+    } else {
+        Err(errors)
+    }
+}
 
-    let board_numbers = file_content
-        .split_whitespace()
-        .skip(1)
-        .map(|num_str| num_str.parse::<usize>().expect("invalid number"))
+// Numbers appearing more than once on the board, sorted
+fn duplicate_numbers(board: &NumberBoard) -> Vec<usize> {
+    let mut numbers = board.iter().copied().collect::<Vec<usize>>();
+    numbers.sort_unstable();
+    let mut duplicates = numbers
+        .windows(2)
+        .filter(|pair| pair[0] == pair[1])
+        .map(|pair| pair[0])
         .collect::<Vec<usize>>();
+    duplicates.dedup();
+    duplicates
+}
 
-    let board_count = board_numbers.len() / BOARD_SIZE;
-    assert_eq!(board_numbers.len(), board_count * BOARD_SIZE);
-    board_numbers.iter();
+fn main() {
+    // --rules rows,columns,diagonals,corners,full picks the winning lines
+    let rules = env::args()
+        .skip_while(|arg| arg != "--rules")
+        .nth(1)
+        .map(|rules| {
+            rules
+                .split(',')
+                .map(WinRule::parse)
+                .collect::<Result<Vec<WinRule>, String>>()
+                .expect("Invalid rules")
+        })
+        .unwrap_or_else(|| STANDARD_RULES.to_vec());
+    let print_timeline = env::args().any(|arg| arg == "--timeline");
 
-    let mut boards = vec![NumberBoard::default(); board_count];
-    board_numbers
-        .chunks(BOARD_SIZE)
-        .zip(boards.iter_mut())
-        .for_each(|(chunk, board)| board.copy_from_slice(chunk));
+    let file_content = fs::read_to_string("2021/4/input.txt").expect("Cannot read input file");
+    let Game { draws, boards } = parse_game(&file_content).unwrap_or_else(|errors| {
+        errors.iter().for_each(|error| eprintln!("{}", error));
+        panic!("Invalid input");
+    });
+    for (index, board) in boards.iter().enumerate() {
+        let duplicates = duplicate_numbers(board);
+        if !duplicates.is_empty() {
+            eprintln!(
+                "Warning: board {} repeats {:?}, every occurrence is marked",
+                index, duplicates
+            );
+        }
+    }
+    let (width, height) = (boards[0].width(), boards[0].height());
+    let bingo = Bingo::new(width, height, &rules);
 
     let lookup = build_lookup(&boards);
 
-    print_solution(1, part1(&guesses, &boards, &lookup));
-    print_solution(2, part2(&guesses, &boards, &lookup));
+    let timeline = simulate(&draws, &boards, &lookup, &bingo);
+    if print_timeline {
+        for (place, win) in timeline.iter().enumerate() {
+            println!(
+                "Win {}: board {} on turn {} with number {}, line {:?}, score {}",
+                place + 1,
+                win.board,
+                win.turn,
+                win.number,
+                win.line,
+                win.score
+            );
+        }
+    }
+
+    print_solution(1, part1(&timeline));
+    print_solution(2, part2(&timeline, boards.len()));
 }
@@ -1,43 +1,139 @@
-use std::{collections::HashMap, fs};
+use std::{env, fs, ops::AddAssign};
 
 use common::utility::print_solution;
 
-fn lanternfishes_after_days(
-    fishes: &HashMap<usize /*days*/, usize /*count*/>,
-    days: usize,
-) -> usize {
-    let mut today_fishes = fishes.clone();
-    let mut tomorrow_fishes = HashMap::new();
-    for _ in 0..days {
-        today_fishes.iter().for_each(|(days, count)| match *days {
-            x if x != 0 => {
-                *tomorrow_fishes.entry(x - 1).or_default() += count;
-            }
-            0 => {
-                *tomorrow_fishes.entry(6).or_default() += count;
-                *tomorrow_fishes.entry(8).or_default() += count;
-            }
-            _ => {
-                panic!("Unexpected value");
-            }
-        });
+const TIMER_BUCKETS: usize = 9;
+const RESET_TIMER: usize = 6;
+const NEWBORN_TIMER: usize = 8;
+
+type Population = [usize; TIMER_BUCKETS];
+
+// Generic over the count type so that the power iteration can reuse it with floats
+fn step_population<T: Copy + Default + AddAssign>(
+    population: &[T; TIMER_BUCKETS],
+) -> [T; TIMER_BUCKETS] {
+    let mut next = [T::default(); TIMER_BUCKETS];
+    next[..TIMER_BUCKETS - 1].copy_from_slice(&population[1..]);
+    next[RESET_TIMER] += population[0];
+    next[NEWBORN_TIMER] += population[0];
+    next
+}
+
+fn lanternfishes_after_days(population: &Population, days: usize) -> usize {
+    (0..days)
+        .fold(*population, |population, _| step_population(&population))
+        .iter()
+        .sum()
+}
 
-        std::mem::swap(&mut today_fishes, &mut tomorrow_fishes);
-        tomorrow_fishes.clear();
+// The first element is the initial population, so the result has days + 1 elements
+fn population_history(initial: &Population, days: usize) -> Vec<Population> {
+    let mut population = *initial;
+    let mut history = Vec::with_capacity(days + 1);
+    history.push(population);
+    for _ in 0..days {
+        population = step_population(&population);
+        history.push(population);
     }
-    today_fishes
+    history
+}
+
+fn history_to_csv(history: &[Population]) -> String {
+    let mut csv = String::from("day");
+    (0..TIMER_BUCKETS).for_each(|timer| csv.push_str(&format!(",timer{}", timer)));
+    csv.push_str(",total\n");
+    history.iter().enumerate().for_each(|(day, population)| {
+        csv.push_str(&day.to_string());
+        population
+            .iter()
+            .for_each(|count| csv.push_str(&format!(",{}", count)));
+        csv.push_str(&format!(",{}\n", population.iter().sum::<usize>()));
+    });
+    csv
+}
+
+fn history_to_json(history: &[Population]) -> String {
+    let days = history
         .iter()
-        .fold(0, |acc, (_days, count)| acc + count)
+        .enumerate()
+        .map(|(day, population)| {
+            format!(
+                "    {{\"day\": {}, \"timers\": [{}], \"total\": {}}}",
+                day,
+                population
+                    .iter()
+                    .map(|count| count.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                population.iter().sum::<usize>()
+            )
+        })
+        .collect::<Vec<String>>();
+    format!("[\n{}\n]\n", days.join(",\n"))
+}
+
+// Estimated by power iteration on the transition matrix of step_population
+fn dominant_eigenvalue() -> f64 {
+    const ITERATIONS: usize = 1000;
+    let mut vector = [1f64; TIMER_BUCKETS];
+    let mut eigenvalue = 0f64;
+    for _ in 0..ITERATIONS {
+        let next = step_population(&vector);
+        let norm = next.iter().map(|v| v * v).sum::<f64>().sqrt();
+        let previous_norm = vector.iter().map(|v| v * v).sum::<f64>().sqrt();
+        eigenvalue = norm / previous_norm;
+        vector = next.map(|v| v / norm);
+    }
+    eigenvalue
+}
+
+// The daily rate oscillates around the eigenvalue, so it is averaged over the last `window` days
+fn growth_rate(history: &[Population], window: usize) -> Option<f64> {
+    let total = |population: &Population| population.iter().sum::<usize>() as f64;
+    let last = total(history.last()?);
+    let first = total(history.iter().rev().nth(window)?);
+    if first > 0f64 {
+        Some((last / first).powf(1f64 / window as f64))
+    } else {
+        None
+    }
 }
 
 fn main() {
+    // --history csv|json PATH writes the population of every day to a file
+    let mut args = env::args().skip_while(|arg| arg != "--history").skip(1);
+    let history_export = args
+        .next()
+        .map(|format| (format, args.next().expect("Missing history path")));
+
     let file_content = fs::read_to_string("2021/6/input.txt").expect("Cannot read input file");
-    let mut fishes: HashMap<usize /*days*/, usize /*count*/> = HashMap::new();
+    let mut population = Population::default();
     file_content
+        .trim()
         .split(',')
         .map(|str_num| str_num.parse::<usize>().expect("Invalid input"))
-        .for_each(|days| *fishes.entry(days).or_default() += 1);
+        .for_each(|days| {
+            assert!(days < TIMER_BUCKETS, "Invalid timer");
+            population[days] += 1
+        });
 
-    print_solution(1, lanternfishes_after_days(&fishes, 80));
-    print_solution(2, lanternfishes_after_days(&fishes, 256));
+    print_solution(1, lanternfishes_after_days(&population, 80));
+    print_solution(2, lanternfishes_after_days(&population, 256));
+
+    if let Some((format, path)) = history_export {
+        const GROWTH_RATE_WINDOW: usize = 128;
+        let history = population_history(&population, 256);
+        let export = match format.as_str() {
+            "csv" => history_to_csv(&history),
+            "json" => history_to_json(&history),
+            _ => panic!("Unknown history format '{}'", format),
+        };
+        fs::write(path, export).expect("Cannot write history file");
+        println!(
+            "Growth rate over the last {} days: {:.6}, dominant eigenvalue: {:.6}",
+            GROWTH_RATE_WINDOW,
+            growth_rate(&history, GROWTH_RATE_WINDOW).expect("Not enough days or no fishes"),
+            dominant_eigenvalue()
+        );
+    }
 }
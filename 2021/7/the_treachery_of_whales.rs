@@ -1,96 +1,188 @@
 use std::{collections::HashMap, fs};
 
-use common::utility::{distance, print_solution};
+use common::convex::{minimize_convex, Minimum};
+use common::utility::print_solution;
 
-fn min_max_pos(crabs: &HashMap</*pos*/ usize, /*count*/ usize>) -> (/*min*/ usize, /*max*/ usize) {
-    (
-        *crabs.keys().clone().min().unwrap(),
-        *crabs.keys().max().unwrap(),
-    )
+fn min_max_pos(crabs: &HashMap</*pos*/ i64, /*weight*/ usize>) -> (/*min*/ i64, /*max*/ i64) {
+    (*crabs.keys().min().unwrap(), *crabs.keys().max().unwrap())
 }
 
 fn get_total_fuel(
-    crabs: &HashMap</*pos*/ usize, /*count*/ usize>,
-    aligned_pos: usize,
+    crabs: &HashMap</*pos*/ i64, /*weight*/ usize>,
+    aligned_pos: i64,
     fuel_calc: fn(/*distance*/ usize) -> usize,
 ) -> usize {
-    crabs.iter().fold(0, |total_fuel, (&pos, &count)| {
-        fuel_calc(distance(pos, aligned_pos)) * count + total_fuel
+    crabs.iter().fold(0, |total_fuel, (&pos, &weight)| {
+        let distance = (pos - aligned_pos).unsigned_abs() as usize;
+        fuel_calc(distance) * weight + total_fuel
     })
 }
 
 fn solve(
-    crabs: &HashMap</*pos*/ usize, /*count*/ usize>,
+    crabs: &HashMap</*pos*/ i64, /*weight*/ usize>,
     fuel_calc: fn(/*distance*/ usize) -> usize,
-) -> usize {
+) -> Minimum<usize> {
     let (min_pos, max_pos) = min_max_pos(crabs);
+    let total_fuel = |aligned_pos| get_total_fuel(crabs, aligned_pos, fuel_calc);
 
-    let mut min_total_fuel = get_total_fuel(&crabs, max_pos, fuel_calc);
-
-    for aligned_pos in min_pos..max_pos {
-        min_total_fuel = get_total_fuel(&crabs, aligned_pos, fuel_calc).min(min_total_fuel);
-    }
-    min_total_fuel
+    // A sum of convex functions of the distances is convex, the tests compare with a brute force
+    minimize_convex(min_pos, max_pos, total_fuel).expect("Fuel cost is not convex")
 }
 
-fn part1(crabs: &HashMap</*pos*/ usize, /*count*/ usize>) -> usize {
+fn part1(crabs: &HashMap</*pos*/ i64, /*weight*/ usize>) -> Minimum<usize> {
     solve(crabs, |distance| distance)
 }
 
-fn part2(crabs: &HashMap</*pos*/ usize, /*count*/ usize>) -> usize {
-    solve(crabs, |distance| {
-        let dist = distance as f64;
-        ((dist as f64 + 1f64) / 2f64 * dist) as usize
-    })
+fn part2(crabs: &HashMap</*pos*/ i64, /*weight*/ usize>) -> Minimum<usize> {
+    solve(crabs, triangular)
+}
+
+fn triangular(distance: usize) -> usize {
+    distance * (distance + 1) / 2
 }
 
 // Based on https://github.com/Lalica/Advent-of-Code/blob/master/AoC2021/solutions/day07.py
-fn part1_better(crabs: &Vec<usize>) -> usize {
+// With weights the median is the first position where the cumulated weight reaches the half of
+// the total weight.
+fn part1_better(crabs: &HashMap</*pos*/ i64, /*weight*/ usize>) -> usize {
     let sorted_crabs = {
-        let mut clone = crabs.clone();
-        clone.sort();
-        clone
+        let mut crabs = crabs
+            .iter()
+            .map(|(&pos, &weight)| (pos, weight))
+            .collect::<Vec<_>>();
+        crabs.sort_unstable();
+        crabs
     };
 
-    let middle_index = (sorted_crabs.len() + 1) / 2;
-    let middle_pos = sorted_crabs[middle_index];
-    sorted_crabs
+    let total_weight = sorted_crabs
+        .iter()
+        .map(|(_pos, weight)| weight)
+        .sum::<usize>();
+    let mut cumulated_weight = 0;
+    let (median_pos, _weight) = *sorted_crabs
         .iter()
-        .fold(0, |acc, &pos| distance(pos, middle_pos) + acc)
+        .find(|(_pos, weight)| {
+            cumulated_weight += weight;
+            cumulated_weight * 2 >= total_weight
+        })
+        .unwrap();
+    get_total_fuel(crabs, median_pos, |distance| distance)
 }
 
 // Based on https://cdn.discordapp.com/attachments/541932275068174359/917782745894256640/crab-submarines.pdf
-fn part2_better(crabs: &Vec<usize>) -> usize {
-    let mean = crabs.iter().sum::<usize>() / crabs.len();
-    let solve = |mean| {
-        crabs
-            .iter()
-            .map(|pos| {
-                let dist = distance(mean, *pos);
-                (dist.pow(2) + dist + 1) / 2
-            })
-            .sum::<usize>()
+// The optimum is within 0.5 of the weighted mean, so checking its floor and ceiling is enough.
+fn part2_better(crabs: &HashMap</*pos*/ i64, /*weight*/ usize>) -> usize {
+    let (weighted_sum, total_weight) = crabs
+        .iter()
+        .fold((0i64, 0i64), |(sum, total), (&pos, &weight)| {
+            (sum + pos * weight as i64, total + weight as i64)
+        });
+    let mean = weighted_sum.div_euclid(total_weight);
+    get_total_fuel(crabs, mean, triangular).min(get_total_fuel(crabs, mean + 1, triangular))
+}
+
+// A crab is either `pos` or `pos:weight`, the position can be negative and the weight is
+// positive
+fn parse_crab(crab: &str) -> Result<(/*pos*/ i64, /*weight*/ usize), String> {
+    let (pos, weight) = match crab.trim().split_once(':') {
+        Some((pos, weight)) => (pos, weight),
+        None => (crab.trim(), "1"),
     };
-    solve(mean).min(solve(mean + 1))
+    let pos = pos
+        .parse::<i64>()
+        .map_err(|_| format!("Invalid position '{}'", pos))?;
+    match weight.parse::<usize>() {
+        Ok(weight) if weight > 0 => Ok((pos, weight)),
+        _ => Err(format!("Invalid weight '{}'", weight)),
+    }
 }
 
 fn main() {
     let file_content = fs::read_to_string("2021/7/input.txt").expect("Cannot read input file");
-    let crabs_vec = file_content
+    let crabs = file_content
         .split(',')
-        .map(|str_num| str_num.parse::<usize>().expect("Invalid input"))
-        .collect::<Vec<usize>>();
-
-    let crabs = crabs_vec.iter().fold(
-        HashMap::</*pos*/ usize, /*count*/ usize>::new(),
-        |mut crabs, &pos| {
-            *crabs.entry(pos).or_default() += 1;
-            crabs
-        },
-    );
-
-    print_solution(1, part1(&crabs));
-    print_solution(1, part1_better(&crabs_vec));
-    print_solution(2, part2(&crabs));
-    print_solution(2, part2_better(&crabs_vec));
+        .map(|crab| parse_crab(crab).expect("Invalid input"))
+        .fold(
+            HashMap::</*pos*/ i64, /*weight*/ usize>::new(),
+            |mut crabs, (pos, weight)| {
+                *crabs.entry(pos).or_default() += weight;
+                crabs
+            },
+        );
+
+    let print_minimum = |part, minimum: Minimum<usize>| {
+        print_solution(
+            part,
+            format!("{} (position {})", minimum.cost, minimum.position),
+        )
+    };
+    let (part1, part1_better) = (part1(&crabs), part1_better(&crabs));
+    let (part2, part2_better) = (part2(&crabs), part2_better(&crabs));
+    assert_eq!(part1.cost, part1_better);
+    assert_eq!(part2.cost, part2_better);
+
+    print_minimum(1, part1);
+    print_solution(1, part1_better);
+    print_minimum(2, part2);
+    print_solution(2, part2_better);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::convex::minimize_brute_force;
+
+    fn crabs(input: &str) -> HashMap<i64, usize> {
+        input.split(',').map(|crab| parse_crab(crab).unwrap()).fold(
+            HashMap::new(),
+            |mut crabs, (pos, weight)| {
+                *crabs.entry(pos).or_default() += weight;
+                crabs
+            },
+        )
+    }
+
+    fn brute_force(crabs: &HashMap<i64, usize>, fuel_calc: fn(usize) -> usize) -> usize {
+        let (min_pos, max_pos) = min_max_pos(crabs);
+        minimize_brute_force(min_pos, max_pos, |pos| {
+            get_total_fuel(crabs, pos, fuel_calc)
+        })
+        .unwrap()
+        .cost
+    }
+
+    #[test]
+    fn small_example() {
+        let crabs = crabs("16,1,2,0,4,2,7,1,2,14");
+        assert_eq!(
+            part1(&crabs),
+            Minimum {
+                position: 2,
+                cost: 37
+            }
+        );
+        assert_eq!(
+            part2(&crabs),
+            Minimum {
+                position: 5,
+                cost: 168
+            }
+        );
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        for input in [
+            "16,1,2,0,4,2,7,1,2,14",
+            "-5:3,0,7:2,100",
+            "3",
+            "-8,-8:4,-1,2:10",
+        ] {
+            let crabs = crabs(input);
+            assert_eq!(part1(&crabs).cost, brute_force(&crabs, |distance| distance));
+            assert_eq!(part2(&crabs).cost, brute_force(&crabs, triangular));
+            assert_eq!(part1(&crabs).cost, part1_better(&crabs));
+            assert_eq!(part2(&crabs).cost, part2_better(&crabs));
+        }
+    }
 }
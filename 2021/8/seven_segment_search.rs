@@ -1,158 +1,585 @@
-use std::{
-    collections::{HashMap, HashSet},
-    fs,
-    ops::{Index, Sub},
-};
+use std::{env, fmt, fs, time::Instant};
 
 use common::utility::print_solution;
 
-fn part1(inputs: &Vec<(Vec<HashSet<char>>, Vec<HashSet<char>>)>) -> usize {
-    inputs.iter().fold(0usize, |acc, (_signals, outputs)| {
-        acc + outputs
+// Bit i is set if the wire/segment labelled 'a' + i is lit
+type Segments = u32;
+
+// Wiring[wire] = segment
+type Wiring = Vec<usize>;
+
+const SEVEN_SEGMENT_DIGITS: [(char, &str); 10] = [
+    ('0', "abcefg"),
+    ('1', "cf"),
+    ('2', "acdeg"),
+    ('3', "acdfg"),
+    ('4', "bcdf"),
+    ('5', "abdfg"),
+    ('6', "abdefg"),
+    ('7', "acf"),
+    ('8', "abcdefg"),
+    ('9', "abcdfg"),
+];
+
+struct Display {
+    segment_count: usize,
+    symbols: Vec<(char, Segments)>,
+}
+
+impl Display {
+    fn from_table(segment_count: usize, table: &[(char, &str)]) -> Result<Self, String> {
+        if segment_count > Segments::BITS as usize {
+            return Err(format!("Too many segments ({})", segment_count));
+        }
+        let symbols = table
             .iter()
-            .filter(|&s| match s.len() {
-                2 => true,
-                3 => true,
-                4 => true,
-                7 => true,
-                _ => false,
-            })
+            .map(|&(symbol, segments)| Ok((symbol, parse_segments(segments, segment_count)?)))
+            .collect::<Result<Vec<(char, Segments)>, String>>()?;
+        Ok(Self {
+            segment_count,
+            symbols,
+        })
+    }
+
+    fn seven_segment() -> Self {
+        Self::from_table(7, &SEVEN_SEGMENT_DIGITS).expect("Invalid seven segment table")
+    }
+
+    fn symbol(&self, segments: Segments) -> Option<char> {
+        self.symbols
+            .iter()
+            .find(|&&(_symbol, s)| s == segments)
+            .map(|&(symbol, _segments)| symbol)
+    }
+
+    // Symbols that can be recognized by the number of lit segments
+    fn has_unique_length(&self, segments: Segments) -> bool {
+        self.symbols
+            .iter()
+            .filter(|(_symbol, s)| s.count_ones() == segments.count_ones())
             .count()
+            == 1
+    }
+}
+
+fn parse_segments(segments: &str, segment_count: usize) -> Result<Segments, String> {
+    segments.chars().try_fold(0, |acc, c| {
+        let index = (c as usize).wrapping_sub('a' as usize);
+        if index < segment_count {
+            Ok(acc | 1 << index)
+        } else {
+            Err(format!("Invalid segment '{}' in '{}'", c, segments))
+        }
     })
 }
 
-fn part2(inputs: &Vec<(Vec<HashSet<char>>, Vec<HashSet<char>>)>) -> usize {
-    inputs.iter().fold(0usize, |acc, (signals, outputs)| {
-        let numbers_and_signals = {
-            let mut d2s = HashMap::new();
-            signals.iter().for_each(|s| match s.len() {
-                2 => {
-                    d2s.insert(1, s);
-                }
-                3 => {
-                    d2s.insert(7, s);
-                }
-                4 => {
-                    d2s.insert(4, s);
-                }
-                7 => {
-                    d2s.insert(8, s);
-                }
-                _ => (),
-            });
+fn bits(segments: Segments) -> impl Iterator<Item = usize> {
+    (0..Segments::BITS as usize).filter(move |bit| segments & 1 << bit != 0)
+}
 
-            // 7 - 4 = a
-            let a = d2s.index(&7).sub(d2s.index(&4));
-            assert_eq!(a.len(), 1);
+fn map_segments(wiring: &Wiring, wires: Segments) -> Segments {
+    bits(wires).fold(0, |acc, wire| acc | 1 << wiring[wire])
+}
 
-            // 1 is not subset of |6| = 6
-            signals.iter().filter(|s| s.len() == 6).for_each(|s| {
-                if !d2s.index(&1).is_subset(s) {
-                    d2s.insert(6, s);
-                }
-            });
+// The sorted lengths of the patterns a wire/segment is part of. A wire can only be connected to
+// a segment with the same signature.
+fn signature(patterns: &[Segments], bit: usize) -> Vec<u32> {
+    let mut signature = patterns
+        .iter()
+        .filter(|&&pattern| pattern & 1 << bit != 0)
+        .map(|pattern| pattern.count_ones())
+        .collect::<Vec<u32>>();
+    signature.sort_unstable();
+    signature
+}
 
-            // 1 - 6 = c
-            let c = d2s.index(&1).sub(d2s.index(&6));
-            assert_eq!(c.len(), 1);
+fn format_segments(segments: Segments) -> String {
+    bits(segments)
+        .map(|bit| (b'a' + bit as u8) as char)
+        .collect()
+}
 
-            // 1 - c = f
-            let f = d2s.index(&1).sub(&c);
-            assert_eq!(f.len(), 1);
+// The n-th letter is the segment the n-th wire is connected to
+fn format_wiring(wiring: &Wiring) -> String {
+    wiring
+        .iter()
+        .map(|&segment| (b'a' + segment as u8) as char)
+        .collect()
+}
 
-            // f is not subset of = 2
-            signals.iter().filter(|s| !f.is_subset(s)).for_each(|s| {
-                d2s.insert(2, s);
-            });
+fn symbol_segments(display: &Display) -> Vec<Segments> {
+    display
+        .symbols
+        .iter()
+        .map(|&(_symbol, segments)| segments)
+        .collect()
+}
+
+// Signatures can only be compared if every symbol is shown exactly once
+fn is_complete(display: &Display, signals: &[Segments]) -> bool {
+    signals.len() == display.symbols.len()
+        && signals
+            .iter()
+            .enumerate()
+            .all(|(index, signal)| !signals[..index].contains(signal))
+}
 
-            // 8 - 2 - f = b
-            let b = d2s.index(&8).sub(d2s.index(&2)).sub(&f);
-            assert_eq!(b.len(), 1);
+fn candidate_segments(display: &Display, signals: &[Segments]) -> Vec<Segments> {
+    let symbols = symbol_segments(display);
+    let all_segments = (0..display.segment_count).fold(0, |acc, segment| acc | 1 << segment);
 
-            // c is not subset of |5| = 5
+    // A wire of a pattern can only be connected to a segment of a symbol with the same length
+    let mut candidates = (0..display.segment_count)
+        .map(|wire| {
             signals
                 .iter()
-                .filter(|s| s.len() == 5 && !c.is_subset(s))
-                .for_each(|s| {
-                    d2s.insert(5, s);
-                });
+                .filter(|&&signal| signal & 1 << wire != 0)
+                .fold(all_segments, |acc, signal| {
+                    acc & symbols
+                        .iter()
+                        .filter(|symbol| symbol.count_ones() == signal.count_ones())
+                        .fold(0, |acc, symbol| acc | symbol)
+                })
+        })
+        .collect::<Vec<Segments>>();
 
-            // 2 - 5 - c = e
-            let e = d2s.index(&2).sub(d2s.index(&5)).sub(&c);
-            assert_eq!(e.len(), 1);
+    if is_complete(display, signals) {
+        let segment_signatures = (0..display.segment_count)
+            .map(|segment| signature(&symbols, segment))
+            .collect::<Vec<Vec<u32>>>();
+        candidates
+            .iter_mut()
+            .enumerate()
+            .for_each(|(wire, candidates)| {
+                let wire_signature = signature(signals, wire);
+                *candidates &= segment_signatures
+                    .iter()
+                    .enumerate()
+                    .filter(|(_segment, s)| **s == wire_signature)
+                    .fold(0, |acc, (segment, _s)| acc | 1 << segment);
+            });
+    }
+    candidates
+}
 
-            // e is not subset of |6| = 9
-            signals
-                .iter()
-                .filter(|s| s.len() == 6 && !e.is_subset(s))
-                .for_each(|s| {
-                    d2s.insert(9, s);
-                });
+fn search(
+    display: &Display,
+    patterns: &[Segments],
+    candidates: &[Segments],
+    wiring: &mut Wiring,
+    (assigned_wires, used_segments): (Segments, Segments),
+    limit: usize,
+    wirings: &mut Vec<Wiring>,
+) {
+    // Every pattern whose wires are all assigned has to show a known symbol
+    let consistent = patterns
+        .iter()
+        .filter(|&&pattern| pattern & !assigned_wires == 0)
+        .all(|&pattern| display.symbol(map_segments(wiring, pattern)).is_some());
+    if !consistent {
+        return;
+    }
 
-            // c and f is subset of |5| = 3
-            signals
-                .iter()
-                .filter(|s| s.len() == 5 && c.is_subset(s) && f.is_subset(s))
-                .for_each(|s| {
-                    d2s.insert(3, s);
-                });
+    // Continue with the unassigned wire that has the fewest remaining options
+    let next_wire = (0..display.segment_count)
+        .filter(|wire| assigned_wires & 1 << wire == 0)
+        .min_by_key(|&wire| (candidates[wire] & !used_segments).count_ones());
+    let wire = match next_wire {
+        Some(wire) => wire,
+        None => {
+            wirings.push(wiring.clone());
+            return;
+        }
+    };
 
-            // 4 - 7 - b = d
-            let d = d2s.index(&4).sub(d2s.index(&7)).sub(&b);
-            assert_eq!(d.len(), 1);
+    for segment in bits(candidates[wire] & !used_segments) {
+        if wirings.len() >= limit {
+            return;
+        }
+        wiring[wire] = segment;
+        search(
+            display,
+            patterns,
+            candidates,
+            wiring,
+            (assigned_wires | 1 << wire, used_segments | 1 << segment),
+            limit,
+            wirings,
+        );
+    }
+}
 
-            // d is not subset of |6| = 0
-            signals
+// Finds at most `limit` wirings that show a known symbol for every signal and output
+fn find_wirings(
+    display: &Display,
+    signals: &[Segments],
+    outputs: &[Segments],
+    limit: usize,
+) -> Vec<Wiring> {
+    let candidates = candidate_segments(display, signals);
+    let patterns = signals
+        .iter()
+        .chain(outputs.iter())
+        .copied()
+        .collect::<Vec<Segments>>();
+    let mut wirings = Vec::new();
+    search(
+        display,
+        &patterns,
+        &candidates,
+        &mut vec![0; display.segment_count],
+        (0, 0),
+        limit,
+        &mut wirings,
+    );
+    wirings
+}
+
+const MAX_REPORTED_WIRINGS: usize = 8;
+
+enum Diagnosis {
+    Unique(Wiring),
+    // Contains at most MAX_REPORTED_WIRINGS wirings
+    Ambiguous(Vec<Wiring>),
+    // Every pattern without which the rest of the entry can be shown, empty if no single pattern
+    // is to blame
+    Impossible(Vec<Segments>),
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnosis::Unique(wiring) => write!(f, "unique wiring {}", format_wiring(wiring)),
+            Diagnosis::Ambiguous(wirings) => write!(
+                f,
+                "ambiguous, candidate wirings: {}{}",
+                wirings
+                    .iter()
+                    .map(format_wiring)
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                if wirings.len() >= MAX_REPORTED_WIRINGS {
+                    ", ..."
+                } else {
+                    ""
+                }
+            ),
+            Diagnosis::Impossible(patterns) => match &patterns[..] {
+                [] => write!(f, "impossible, several patterns contradict each other"),
+                [pattern] => write!(
+                    f,
+                    "impossible, pattern '{}' contradicts the others",
+                    format_segments(*pattern)
+                ),
+                patterns => write!(
+                    f,
+                    "impossible, one of the patterns {} contradicts the others",
+                    patterns
+                        .iter()
+                        .map(|&pattern| format!("'{}'", format_segments(pattern)))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            },
+        }
+    }
+}
+
+fn diagnose(display: &Display, signals: &[Segments], outputs: &[Segments]) -> Diagnosis {
+    let mut wirings = find_wirings(display, signals, outputs, MAX_REPORTED_WIRINGS);
+    match wirings.len() {
+        0 => (),
+        1 => return Diagnosis::Unique(wirings.pop().unwrap()),
+        _ => return Diagnosis::Ambiguous(wirings),
+    }
+
+    // Look for the patterns without which the rest of the entry can be shown
+    let patterns = signals.len() + outputs.len();
+    let contradicting = (0..patterns).filter(|&index| {
+        let (signals, outputs) = if index < signals.len() {
+            let mut remaining = signals.to_vec();
+            remaining.remove(index);
+            (remaining, outputs.to_vec())
+        } else {
+            let mut remaining = outputs.to_vec();
+            remaining.remove(index - signals.len());
+            (signals.to_vec(), remaining)
+        };
+        !find_wirings(display, &signals, &outputs, 1).is_empty()
+    });
+    Diagnosis::Impossible(
+        contradicting
+            .map(|index| {
+                signals
+                    .iter()
+                    .chain(outputs.iter())
+                    .nth(index)
+                    .copied()
+                    .unwrap()
+            })
+            .collect(),
+    )
+}
+
+fn decode(display: &Display, wiring: &Wiring, outputs: &[Segments]) -> Option<String> {
+    outputs
+        .iter()
+        .map(|&output| display.symbol(map_segments(wiring, output)))
+        .collect()
+}
+
+fn parse_line(display: &Display, line: &str) -> Result<(Vec<Segments>, Vec<Segments>), String> {
+    let parse_patterns = |patterns: &str| {
+        patterns
+            .split_whitespace()
+            .map(|s| parse_segments(s, display.segment_count))
+            .collect::<Result<Vec<Segments>, String>>()
+    };
+    let (signals, outputs) = line
+        .split_once('|')
+        .ok_or_else(|| format!("missing separator in '{}'", line))?;
+    Ok((parse_patterns(signals)?, parse_patterns(outputs)?))
+}
+
+const SIGNAL_COUNT: usize = 10;
+const OUTPUT_COUNT: usize = 4;
+
+// Seven segment patterns fit into a byte, so an entry doesn't need any heap allocation
+type Pattern = u8;
+
+#[derive(Clone, Copy)]
+struct Entry {
+    signals: [Pattern; SIGNAL_COUNT],
+    outputs: [Pattern; OUTPUT_COUNT],
+}
+
+impl Entry {
+    fn new(signals: &[Segments], outputs: &[Segments]) -> Option<Self> {
+        let to_patterns = |segments: &[Segments]| {
+            segments
                 .iter()
-                .filter(|s| s.len() == 6 && !d.is_subset(s))
-                .for_each(|s| {
-                    d2s.insert(0, s);
-                });
-
-            // 8 - 4 - 1 - b = g
-            let g = d2s
-                .index(&8)
-                .sub(d2s.index(&7))
-                .sub(d2s.index(&4))
-                .sub(d2s.index(&1))
-                .sub(&e);
-            assert_eq!(g.len(), 1);
-            d2s.into_iter().collect::<Vec<(usize, &HashSet<char>)>>()
+                .map(|&s| Pattern::try_from(s).ok())
+                .collect::<Option<Vec<Pattern>>>()
         };
+        Some(Self {
+            signals: to_patterns(signals)?.try_into().ok()?,
+            outputs: to_patterns(outputs)?.try_into().ok()?,
+        })
+    }
+}
+
+// Every wire is scored by the number of signals it is part of, and every pattern by the sum of
+// the scores of its wires. The scores don't depend on the wiring, so if they are unique for the
+// symbols of a display, a pattern can be decoded by its score alone.
+struct DecodingTable {
+    symbols_by_score: Vec<Option<char>>,
+}
+
+impl DecodingTable {
+    fn new(display: &Display) -> Option<Self> {
+        let symbols = symbol_segments(display);
+        let segment_scores = (0..display.segment_count)
+            .map(|segment| signature(&symbols, segment).len())
+            .collect::<Vec<usize>>();
+
+        let mut symbols_by_score = vec![None; segment_scores.iter().sum::<usize>() + 1];
+        for &(symbol, segments) in &display.symbols {
+            let score = bits(segments).map(|s| segment_scores[s]).sum::<usize>();
+            if symbols_by_score[score].replace(symbol).is_some() {
+                return None;
+            }
+        }
+        Some(Self { symbols_by_score })
+    }
+
+    // The symbols of the outputs, turning them into a value is up to the caller
+    fn decode(&self, entry: &Entry) -> Option<[char; OUTPUT_COUNT]> {
+        let mut wire_scores = [0usize; Pattern::BITS as usize];
+        for signal in entry.signals {
+            for (wire, score) in wire_scores.iter_mut().enumerate() {
+                *score += usize::from(signal >> wire & 1);
+            }
+        }
+        let mut symbols = [' '; OUTPUT_COUNT];
+        for (symbol, &output) in symbols.iter_mut().zip(entry.outputs.iter()) {
+            let score = (0..Pattern::BITS as usize)
+                .filter(|wire| output >> wire & 1 != 0)
+                .map(|wire| wire_scores[wire])
+                .sum::<usize>();
+            *symbol = self.symbols_by_score.get(score).copied()??;
+        }
+        Some(symbols)
+    }
+}
+
+fn decode_batch(table: &DecodingTable, entries: &[Entry]) -> Vec<Option<[char; OUTPUT_COUNT]>> {
+    entries.iter().map(|entry| table.decode(entry)).collect()
+}
+
+// Linear congruential generator, good enough to shuffle test entries
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % bound as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            values.swap(i, self.next(i + 1));
+        }
+    }
+}
 
-        acc + outputs.iter().fold(0, |acc, signals| {
-            numbers_and_signals
+// Generates randomly wired entries of the display together with their expected output symbols
+fn generate_entries(
+    display: &Display,
+    count: usize,
+    seed: u64,
+) -> Vec<(Entry, [char; OUTPUT_COUNT])> {
+    let mut random = Random(seed);
+    let mut wiring = (0..display.segment_count).collect::<Wiring>();
+    (0..count)
+        .map(|_| {
+            random.shuffle(&mut wiring);
+            let mut signals = display.symbols.clone();
+            random.shuffle(&mut signals);
+            let outputs = (0..OUTPUT_COUNT)
+                .map(|_| signals[random.next(signals.len())])
+                .collect::<Vec<(char, Segments)>>();
+            let pattern = |segments| map_segments(&wiring, segments) as Pattern;
+            let entry = Entry {
+                signals: signals
+                    .iter()
+                    .map(|&(_symbol, segments)| pattern(segments))
+                    .collect::<Vec<Pattern>>()
+                    .try_into()
+                    .expect("Unexpected symbol count"),
+                outputs: outputs
+                    .iter()
+                    .map(|&(_symbol, segments)| pattern(segments))
+                    .collect::<Vec<Pattern>>()
+                    .try_into()
+                    .expect("Unexpected output count"),
+            };
+            let symbols = outputs
                 .iter()
-                .filter(|(_num, sig)| sig.eq(&signals))
-                .next()
-                .expect("Unexpected output")
-                .0
-                + acc * 10
+                .map(|&(symbol, _segments)| symbol)
+                .collect::<Vec<char>>()
+                .try_into()
+                .expect("Unexpected output count");
+            (entry, symbols)
         })
+        .collect()
+}
+
+fn stress_test(display: &Display, table: &DecodingTable, count: usize) {
+    let generated = generate_entries(display, count, count as u64);
+    let entries = generated
+        .iter()
+        .map(|&(entry, _symbols)| entry)
+        .collect::<Vec<Entry>>();
+
+    let start = Instant::now();
+    let decoded = decode_batch(table, &entries);
+    let elapsed = start.elapsed();
+
+    let mismatches = decoded
+        .iter()
+        .zip(generated.iter())
+        .filter(|&(decoded, &(_entry, expected))| *decoded != Some(expected))
+        .count();
+    println!(
+        "Decoded {} entries in {:?}, {} mismatches",
+        count, elapsed, mismatches
+    );
+}
+
+fn part1(display: &Display, decodable: &[(Wiring, Vec<Segments>)]) -> usize {
+    decodable.iter().fold(0usize, |acc, (_wiring, outputs)| {
+        acc + outputs
+            .iter()
+            .filter(|&&output| display.has_unique_length(output))
+            .count()
     })
 }
 
+fn part2(display: &Display, decodable: &[(Wiring, Vec<Segments>)]) -> u64 {
+    decodable.iter().fold(0, |acc, (wiring, outputs)| {
+        acc + decode(display, wiring, outputs)
+            .expect("Cannot decode entry")
+            .parse::<u64>()
+            .expect("Unexpected output")
+    })
+}
+
+fn part2_batch(table: &DecodingTable, entries: &[Entry]) -> u64 {
+    decode_batch(table, entries)
+        .into_iter()
+        .map(|symbols| {
+            symbols
+                .expect("Cannot decode entry")
+                .iter()
+                .collect::<String>()
+                .parse::<u64>()
+                .expect("Unexpected output")
+        })
+        .sum()
+}
+
 fn main() {
+    let stress_count = env::args()
+        .skip_while(|arg| arg != "--stress")
+        .nth(1)
+        .map(|count| count.parse::<usize>().expect("Invalid entry count"));
+
     let file_content = fs::read_to_string("2021/8/input.txt").expect("Cannot read input file");
-    let inputs = file_content
-        .lines()
-        .map(|line| {
-            let vec = line.split('|').collect::<Vec<&str>>();
-            (
-                vec[0]
-                    .trim()
-                    .split(' ')
-                    .map(|s| s.chars().collect::<HashSet<char>>())
-                    .collect::<Vec<HashSet<char>>>(),
-                vec[1]
-                    .trim()
-                    .split(' ')
-                    .map(|s| s.chars().collect::<HashSet<char>>())
-                    .collect::<Vec<HashSet<char>>>(),
-            )
-        })
-        .collect::<Vec<(Vec<HashSet<char>>, Vec<HashSet<char>>)>>();
+    let display = Display::seven_segment();
+    let table = DecodingTable::new(&display).expect("Display cannot be decoded by scores");
+
+    // Entries that cannot be decoded are reported and left out of the results. The batch decoding
+    // only handles entries with the usual pattern counts.
+    let mut decodable = Vec::new();
+    let mut entries = Vec::new();
+    let mut unusual_entries = 0;
+    for (index, line) in file_content.lines().enumerate() {
+        let (signals, outputs) = match parse_line(&display, line) {
+            Ok(patterns) => patterns,
+            Err(error) => {
+                eprintln!("Line {}: invalid input, {}", index + 1, error);
+                continue;
+            }
+        };
+        match diagnose(&display, &signals, &outputs) {
+            Diagnosis::Unique(wiring) => {
+                match Entry::new(&signals, &outputs) {
+                    Some(entry) => entries.push(entry),
+                    None => unusual_entries += 1,
+                }
+                decodable.push((wiring, outputs));
+            }
+            diagnosis => eprintln!("Line {}: {}", index + 1, diagnosis),
+        }
+    }
+
+    print_solution(1, part1(&display, &decodable));
+    print_solution(2, part2(&display, &decodable));
+    match unusual_entries {
+        0 => print_solution(2, part2_batch(&table, &entries)),
+        count => print_solution(
+            2,
+            format!(
+                "batch decoding unavailable, {} entries don't have {} signals and {} outputs",
+                count, SIGNAL_COUNT, OUTPUT_COUNT
+            ),
+        ),
+    }
 
-    print_solution(1, part1(&inputs));
-    print_solution(2, part2(&inputs));
+    if let Some(count) = stress_count {
+        stress_test(&display, &table, count);
+    }
 }
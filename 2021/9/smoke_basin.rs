@@ -1,109 +1,291 @@
-use std::{collections::HashSet, fs};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+};
 
+use common::grid::{Grid, ORTHOGONAL_OFFSETS};
 use common::utility::print_solution;
 
-const NEIGHBOR_OFFSETS: [(i8, i8); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+fn find_low_points(depths: &Grid<usize>) -> HashSet<(usize, usize)> {
+    depths
+        .positions()
+        .filter(|&pos| {
+            depths
+                .neighbors(pos, &ORTHOGONAL_OFFSETS)
+                .all(|neighbor| depths[neighbor] > depths[pos])
+        })
+        .collect()
+}
 
-fn get_neighbor_fields(height: usize, width: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
-    let mut neighbors = Vec::new();
+struct DisjointSets {
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+}
 
-    for offset in &NEIGHBOR_OFFSETS {
-        let (r, c) = (
-            row.wrapping_add(offset.0 as usize),
-            col.wrapping_add(offset.1 as usize),
-        );
-        if r < height && c < width {
-            neighbors.push((r, c));
+impl DisjointSets {
+    fn new(count: usize) -> Self {
+        Self {
+            parents: (0..count).collect(),
+            sizes: vec![1; count],
         }
     }
 
-    neighbors
-}
+    fn find(&mut self, mut element: usize) -> usize {
+        while self.parents[element] != element {
+            self.parents[element] = self.parents[self.parents[element]];
+            element = self.parents[element];
+        }
+        element
+    }
 
-#[allow(dead_code)]
-fn print_basins(height: usize, width: usize, basins: &HashSet<(usize, usize)>) {
-    for row in 0..height {
-        for col in 0..width {
-            let char = if basins.contains(&(row, col)) {
-                'X'
-            } else {
-                'O'
-            };
-            print!("{}", char);
+    fn union(&mut self, lhs: usize, rhs: usize) {
+        let (mut lhs, mut rhs) = (self.find(lhs), self.find(rhs));
+        if lhs == rhs {
+            return;
         }
-        println!("");
+        if self.sizes[lhs] < self.sizes[rhs] {
+            std::mem::swap(&mut lhs, &mut rhs);
+        }
+        self.parents[rhs] = lhs;
+        self.sizes[lhs] += self.sizes[rhs];
     }
 }
 
-fn find_low_points(depths: &Vec<Vec<usize>>) -> HashSet<(usize, usize)> {
-    let mut low_points = HashSet::<(usize, usize)>::new();
-    let width = depths[0].len();
-    let height = depths.len();
-    for col in 0..width {
-        for row in 0..height {
-            let val = depths[row][col];
-            if get_neighbor_fields(height, width, row, col)
-                .iter()
-                .all(|&(r, c)| depths[r][c] > val)
-            {
-                low_points.insert((row, col));
+// Assigns every cell lower than the wall height to a basin, basins are numbered from 0 in the
+// order of their first cell
+fn label_basins(depths: &Grid<usize>, wall_height: usize) -> Grid<Option<usize>> {
+    let flat_index = |(row, col): (usize, usize)| row * depths.width() + col;
+    let mut sets = DisjointSets::new(depths.width() * depths.height());
+    for pos in depths.positions().filter(|&pos| depths[pos] < wall_height) {
+        // Joining with the right and bottom neighbors is enough to cover every edge
+        for neighbor in [(pos.0, pos.1 + 1), (pos.0 + 1, pos.1)] {
+            if depths.get(neighbor).is_some_and(|&d| d < wall_height) {
+                sets.union(flat_index(pos), flat_index(neighbor));
             }
         }
     }
-    low_points
+
+    let mut labels = HashMap::new();
+    let mut basins = depths.map(|_| None);
+    for pos in depths.positions().filter(|&pos| depths[pos] < wall_height) {
+        let root = sets.find(flat_index(pos));
+        let next_label = labels.len();
+        basins[pos] = Some(*labels.entry(root).or_insert(next_label));
+    }
+    basins
 }
 
-fn part1(depths: &Vec<Vec<usize>>, low_points: &HashSet<(usize, usize)>) -> usize {
-    low_points
-        .iter()
-        .fold(0, |acc, &(r, c)| acc + depths[r][c] + 1)
-}
-
-fn part2(depths: &Vec<Vec<usize>>, low_points: &HashSet<(usize, usize)>) -> usize {
-    let width = depths[0].len();
-    let height = depths.len();
-    let mut basin_sizes = Vec::new();
-    let mut visited = HashSet::new();
-    for basin in low_points {
-        let mut size = 0;
-        let mut coords_to_check = vec![(basin.0, basin.1, depths[basin.0][basin.1])];
-        while !coords_to_check.is_empty() {
-            let (r, c, expected) = coords_to_check.pop().unwrap();
-            let value = depths[r][c];
-            if value >= expected && !visited.contains(&(r, c)) && value != 9 {
-                coords_to_check.append(
-                    &mut get_neighbor_fields(height, width, r, c)
-                        .into_iter()
-                        .map(|(r, c)| (r, c, expected + 1))
-                        .collect::<Vec<(usize, usize, usize)>>(),
-                );
-                size += 1;
-                visited.insert((r, c));
+#[derive(Debug)]
+struct BoundingBox {
+    top: usize,
+    left: usize,
+    bottom: usize,
+    right: usize,
+}
+
+#[derive(Debug)]
+struct Basin {
+    low_point: (usize, usize),
+    size: usize,
+    // depth_histogram[depth] is the number of cells with the given depth
+    depth_histogram: Vec<usize>,
+    bounding_box: BoundingBox,
+}
+
+fn basin_report(
+    depths: &Grid<usize>,
+    basins: &Grid<Option<usize>>,
+    wall_height: usize,
+) -> Vec<Basin> {
+    let mut report: Vec<Basin> = Vec::new();
+    for pos in depths.positions() {
+        let label = match basins[pos] {
+            Some(label) => label,
+            None => continue,
+        };
+        if label == report.len() {
+            report.push(Basin {
+                low_point: pos,
+                size: 0,
+                depth_histogram: vec![0; wall_height],
+                bounding_box: BoundingBox {
+                    top: pos.0,
+                    left: pos.1,
+                    bottom: pos.0,
+                    right: pos.1,
+                },
+            });
+        }
+
+        let basin = &mut report[label];
+        if depths[pos] < depths[basin.low_point] {
+            basin.low_point = pos;
+        }
+        basin.size += 1;
+        basin.depth_histogram[depths[pos]] += 1;
+        let bounding_box = &mut basin.bounding_box;
+        bounding_box.top = bounding_box.top.min(pos.0);
+        bounding_box.left = bounding_box.left.min(pos.1);
+        bounding_box.bottom = bounding_box.bottom.max(pos.0);
+        bounding_box.right = bounding_box.right.max(pos.1);
+    }
+    report
+}
+
+fn print_basin_report(report: &[Basin]) {
+    for (label, basin) in report.iter().enumerate() {
+        let b = &basin.bounding_box;
+        println!(
+            "Basin {}: low point {:?}, size {}, depths {:?}, bounding box ({}, {})-({}, {})",
+            label,
+            basin.low_point,
+            basin.size,
+            basin.depth_histogram,
+            b.top,
+            b.left,
+            b.bottom,
+            b.right
+        );
+    }
+}
+
+fn part1(depths: &Grid<usize>, low_points: &HashSet<(usize, usize)>) -> usize {
+    low_points.iter().fold(0, |acc, &pos| acc + depths[pos] + 1)
+}
+
+// Labels of the `count` largest basins in decreasing order of size
+fn largest_basins(report: &[Basin], count: usize) -> Vec<usize> {
+    let mut labels = (0..report.len()).collect::<Vec<usize>>();
+    labels.sort_by(|&lhs, &rhs| report[rhs].size.cmp(&report[lhs].size));
+    labels.truncate(count);
+    labels
+}
+
+const WALL_COLOR: u8 = 240;
+
+// Picks from the brighter 4x4x4 part of the 6x6x6 color cube of the 256 color palette, the
+// stride is coprime to 64 to make neighboring labels look different
+fn basin_color(label: usize) -> u8 {
+    let index = label * 23 % 64;
+    let (r, g, b) = (2 + index / 16, 2 + index / 4 % 4, 2 + index % 4);
+    (16 + 36 * r + 6 * g + b) as u8
+}
+
+fn color_to_rgb(color: u8) -> [u8; 3] {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match color {
+        16..=231 => {
+            let index = (color - 16) as usize;
+            [LEVELS[index / 36], LEVELS[index / 6 % 6], LEVELS[index % 6]]
+        }
+        232..=255 => [8 + (color - 232) * 10; 3],
+        _ => panic!("Unexpected color {}", color),
+    }
+}
+
+// The largest basins are drawn with background color, the low points are inverted
+fn render_basins_ansi(
+    depths: &Grid<usize>,
+    basins: &Grid<Option<usize>>,
+    low_points: &HashSet<(usize, usize)>,
+    largest: &[usize],
+) -> String {
+    let mut rendered = String::new();
+    for row in 0..depths.height() {
+        for col in 0..depths.width() {
+            let pos = (row, col);
+            let style = match basins[pos] {
+                Some(label) if largest.contains(&label) => {
+                    format!("\x1b[30;48;5;{}m", basin_color(label))
+                }
+                Some(label) => format!("\x1b[38;5;{}m", basin_color(label)),
+                None => format!("\x1b[38;5;{}m", WALL_COLOR),
+            };
+            rendered.push_str(&style);
+            if low_points.contains(&pos) {
+                rendered.push_str("\x1b[7m");
             }
+            rendered.push_str(&format!("{}\x1b[0m", depths[pos]));
         }
-        basin_sizes.push(size);
+        rendered.push('\n');
     }
-    basin_sizes.sort_by(|lhs, rhs| rhs.cmp(lhs));
-    basin_sizes.iter().take(3).fold(1, |acc, value| acc * value)
+    rendered
+}
+
+// Binary PPM where every cell is a square of `scale` pixels. Walls are black, low points white,
+// and every basin but the largest ones is dimmed.
+fn render_basins_ppm(
+    basins: &Grid<Option<usize>>,
+    low_points: &HashSet<(usize, usize)>,
+    largest: &[usize],
+    scale: usize,
+) -> Vec<u8> {
+    let (width, height) = (basins.width() * scale, basins.height() * scale);
+    let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for row in 0..height {
+        for col in 0..width {
+            let pos = (row / scale, col / scale);
+            let rgb = match basins[pos] {
+                _ if low_points.contains(&pos) => [255; 3],
+                Some(label) if largest.contains(&label) => color_to_rgb(basin_color(label)),
+                Some(label) => color_to_rgb(basin_color(label)).map(|c| c / 2),
+                None => [0; 3],
+            };
+            image.extend_from_slice(&rgb);
+        }
+    }
+    image
+}
+
+fn part2(report: &[Basin]) -> usize {
+    largest_basins(report, 3)
+        .iter()
+        .map(|&label| report[label].size)
+        .product()
 }
 
 fn main() {
+    let mut args = env::args().skip(1);
+    let mut wall_height = 9;
+    let mut print_report = false;
+    let mut render = false;
+    let mut ppm_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--wall-height" => {
+                wall_height = args
+                    .next()
+                    .and_then(|height| height.parse::<usize>().ok())
+                    .expect("Invalid wall height")
+            }
+            "--report" => print_report = true,
+            "--render" => render = true,
+            "--ppm" => ppm_path = Some(args.next().expect("Missing PPM path")),
+            _ => panic!("Unknown argument '{}'", arg),
+        }
+    }
+
     let file_content = fs::read_to_string("2021/9/input.txt").expect("Cannot read input file");
-    let depths = file_content
-        .lines()
-        .map(|line| {
-            line.chars()
-                .map(|c| {
-                    c.to_digit(10)
-                        .expect("Invalid depth")
-                        .try_into()
-                        .expect("Invalid depth")
-                })
-                .collect::<Vec<usize>>()
-        })
-        .collect::<Vec<Vec<usize>>>();
+    let depths = Grid::parse_digits(&file_content).expect("Invalid depth");
 
     let low_points = find_low_points(&depths);
+    let basins = label_basins(&depths, wall_height);
+    let report = basin_report(&depths, &basins, wall_height);
+    if print_report {
+        print_basin_report(&report);
+    }
+    let largest = largest_basins(&report, 3);
+    if render {
+        print!(
+            "{}",
+            render_basins_ansi(&depths, &basins, &low_points, &largest)
+        );
+    }
+    if let Some(path) = ppm_path {
+        const PPM_SCALE: usize = 4;
+        let image = render_basins_ppm(&basins, &low_points, &largest, PPM_SCALE);
+        fs::write(path, image).expect("Cannot write PPM file");
+    }
     print_solution(1, part1(&depths, &low_points));
-    print_solution(2, part2(&depths, &low_points));
+    print_solution(2, part2(&report));
 }
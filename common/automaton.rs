@@ -0,0 +1,176 @@
+use crate::grid::Grid;
+
+// Rules where charged cells trigger once their threshold is reached, and every triggered cell
+// charges its neighbors, possibly triggering them in the same step.
+pub trait CascadeRule {
+    type Cell;
+
+    // Applied to every cell at the beginning of a step
+    fn charge(&self, cell: &mut Self::Cell);
+    fn is_triggered(&self, cell: &Self::Cell) -> bool;
+    // Applied to every neighbor of a triggered cell
+    fn propagate(&self, neighbor: &mut Self::Cell);
+    // Applied to every triggered cell at the end of a step
+    fn reset(&self, cell: &mut Self::Cell);
+}
+
+// Rules where the next state of a cell depends only on its state and the number of its alive
+// neighbors, e.g. Game of Life
+pub trait LifeRule {
+    type Cell;
+
+    fn is_alive(&self, cell: &Self::Cell) -> bool;
+    fn next(&self, cell: &Self::Cell, alive_neighbors: usize) -> Self::Cell;
+}
+
+pub struct Conway;
+
+impl LifeRule for Conway {
+    type Cell = bool;
+
+    fn is_alive(&self, cell: &bool) -> bool {
+        *cell
+    }
+
+    fn next(&self, cell: &bool, alive_neighbors: usize) -> bool {
+        matches!((cell, alive_neighbors), (true, 2) | (_, 3))
+    }
+}
+
+pub struct StepEvent<'a, T> {
+    // 1 for the first step
+    pub step: usize,
+    pub grid: &'a Grid<T>,
+    // Cells triggered by a cascade step in the order of triggering, empty for life steps
+    pub triggered: &'a [(usize, usize)],
+}
+
+type StepHook<'a, T> = Box<dyn FnMut(&StepEvent<T>) + 'a>;
+
+pub struct Automaton<'a, T> {
+    grid: Grid<T>,
+    offsets: &'a [(i8, i8)],
+    steps: usize,
+    hooks: Vec<StepHook<'a, T>>,
+}
+
+impl<'a, T> Automaton<'a, T> {
+    pub fn new(grid: Grid<T>, offsets: &'a [(i8, i8)]) -> Self {
+        Self {
+            grid,
+            offsets,
+            steps: 0,
+            hooks: Vec::new(),
+        }
+    }
+
+    pub fn grid(&self) -> &Grid<T> {
+        &self.grid
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    // Hooks are called after every step
+    pub fn add_hook<F: FnMut(&StepEvent<T>) + 'a>(&mut self, hook: F) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    fn finish_step(&mut self, triggered: &[(usize, usize)]) {
+        self.steps += 1;
+        let event = StepEvent {
+            step: self.steps,
+            grid: &self.grid,
+            triggered,
+        };
+        self.hooks.iter_mut().for_each(|hook| hook(&event));
+    }
+
+    // Returns the triggered cells in the order of triggering
+    pub fn cascade_step<R: CascadeRule<Cell = T>>(&mut self, rule: &R) -> Vec<(usize, usize)> {
+        let mut is_triggered = self.grid.map(|_| false);
+        let mut triggered = Vec::new();
+        for pos in self.grid.positions() {
+            rule.charge(&mut self.grid[pos]);
+            if rule.is_triggered(&self.grid[pos]) {
+                is_triggered[pos] = true;
+                triggered.push(pos);
+            }
+        }
+
+        let mut next_to_propagate = 0;
+        while next_to_propagate < triggered.len() {
+            let pos = triggered[next_to_propagate];
+            next_to_propagate += 1;
+            for neighbor in self.grid.neighbors(pos, self.offsets) {
+                rule.propagate(&mut self.grid[neighbor]);
+                if !is_triggered[neighbor] && rule.is_triggered(&self.grid[neighbor]) {
+                    is_triggered[neighbor] = true;
+                    triggered.push(neighbor);
+                }
+            }
+        }
+
+        triggered
+            .iter()
+            .for_each(|&pos| rule.reset(&mut self.grid[pos]));
+        self.finish_step(&triggered);
+        triggered
+    }
+
+    pub fn life_step<R: LifeRule<Cell = T>>(&mut self, rule: &R) {
+        let next = self
+            .grid
+            .positions()
+            .map(|pos| {
+                let alive_neighbors = self
+                    .grid
+                    .neighbors(pos, self.offsets)
+                    .filter(|&neighbor| rule.is_alive(&self.grid[neighbor]))
+                    .count();
+                rule.next(&self.grid[pos], alive_neighbors)
+            })
+            .collect::<Vec<T>>();
+        self.grid
+            .iter_mut()
+            .zip(next)
+            .for_each(|(cell, next)| *cell = next);
+        self.finish_step(&[]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::ALL_OFFSETS;
+
+    fn parse(rows: &[&str]) -> Grid<bool> {
+        Grid::from_rows(
+            rows.iter()
+                .map(|row| row.chars().map(|c| c == '#').collect())
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn blinker_oscillates_with_period_2() {
+        let horizontal = parse(&[".....", ".....", ".###.", ".....", "....."]);
+        let vertical = parse(&[".....", "..#..", "..#..", "..#..", "....."]);
+        let mut alive_counts = Vec::new();
+        let mut automaton = Automaton::new(horizontal.clone(), &ALL_OFFSETS);
+        automaton.add_hook(|event| {
+            alive_counts.push(event.grid.iter().filter(|&&alive| alive).count());
+            assert!(event.triggered.is_empty());
+        });
+
+        automaton.life_step(&Conway);
+        assert_eq!(automaton.grid(), &vertical);
+        automaton.life_step(&Conway);
+        assert_eq!(automaton.grid(), &horizontal);
+        assert_eq!(automaton.steps(), 2);
+        drop(automaton);
+        assert_eq!(alive_counts, vec![3, 3]);
+    }
+}
@@ -0,0 +1,120 @@
+// Minimisation of convex functions over integer positions. A function is convex on [min, max]
+// if its differences cost(x + 1) - cost(x) never decrease, e.g. a sum of convex functions of
+// the distances from fixed points.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Minimum<C> {
+    pub position: i64,
+    pub cost: C,
+}
+
+// Binary search on the differences of the cost function: the first position where the cost
+// stops decreasing is a minimum. A convex cost decreases from the start of the range to the
+// minimum and never decreases after it, so the range ends are checked against that. The check
+// is partial: it only evaluates the ends and the found position, so a non-convex cost with
+// several local minima can pass it and return one that is not the global minimum. Callers that
+// cannot prove convexity should compare with `minimize_brute_force`.
+pub fn minimize_convex<C, F>(min: i64, max: i64, cost: F) -> Result<Minimum<C>, String>
+where
+    C: PartialOrd + Copy,
+    F: Fn(i64) -> C,
+{
+    if min > max {
+        return Err(format!("Empty range [{}, {}]", min, max));
+    }
+
+    let (mut low, mut high) = (min, max);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if cost(middle) <= cost(middle + 1) {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+
+    let minimum = Minimum {
+        position: low,
+        cost: cost(low),
+    };
+    let decreases_at_start = low == min || cost(min) > cost(min + 1);
+    let increases_at_end = low == max || cost(max - 1) <= cost(max);
+    let ends_are_higher = cost(min) >= minimum.cost && cost(max) >= minimum.cost;
+    if !(decreases_at_start && increases_at_end && ends_are_higher) {
+        return Err(format!(
+            "Cost function is not convex on [{}, {}], found position {}",
+            min, max, low
+        ));
+    }
+    Ok(minimum)
+}
+
+// Reference implementation that evaluates every position
+pub fn minimize_brute_force<C, F>(min: i64, max: i64, cost: F) -> Option<Minimum<C>>
+where
+    C: PartialOrd + Copy,
+    F: Fn(i64) -> C,
+{
+    (min..=max)
+        .map(|position| Minimum {
+            position,
+            cost: cost(position),
+        })
+        .fold(None, |best: Option<Minimum<C>>, candidate| match best {
+            Some(best) if best.cost <= candidate.cost => Some(best),
+            _ => Some(candidate),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_brute_force_on_convex_costs() {
+        let points = [-7i64, 0, 3, 3, 12];
+        let linear = |x: i64| points.iter().map(|p| (p - x).abs()).sum::<i64>();
+        let quadratic = |x: i64| points.iter().map(|p| (p - x) * (p - x)).sum::<i64>();
+        for (min, max) in [(-10, 15), (0, 0), (4, 20), (-20, -8), (1, 2)] {
+            assert_eq!(
+                minimize_convex(min, max, linear).ok(),
+                minimize_brute_force(min, max, linear)
+            );
+            assert_eq!(
+                minimize_convex(min, max, quadratic).ok(),
+                minimize_brute_force(min, max, quadratic)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_empty_range() {
+        assert!(minimize_convex(1, 0, |x| x).is_err());
+    }
+
+    #[test]
+    fn detects_concave_cost() {
+        assert!(minimize_convex(-5, 5, |x| -x * x).is_err());
+    }
+
+    #[test]
+    fn misses_a_local_minimum() {
+        let costs = [5, 0, 3, 2, 4];
+        let cost = |x: i64| costs[x as usize];
+        let minimum = minimize_convex(0, 4, cost).unwrap();
+        assert_eq!(
+            minimum,
+            Minimum {
+                position: 3,
+                cost: 2
+            }
+        );
+        assert_eq!(
+            minimize_brute_force(0, 4, cost),
+            Some(Minimum {
+                position: 1,
+                cost: 0
+            })
+        );
+    }
+}
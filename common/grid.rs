@@ -0,0 +1,133 @@
+use std::ops::{Index, IndexMut};
+
+// Cells are addressed by (row, column)
+
+pub const ORTHOGONAL_OFFSETS: [(i8, i8); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+pub const ALL_OFFSETS: [(i8, i8); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Result<Self, String> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.len());
+        if let Some(row) = rows.iter().position(|row| row.len() != width) {
+            return Err(format!(
+                "Row {} has {} cells instead of {}",
+                row,
+                rows[row].len(),
+                width
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            cells: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, (row, col): (usize, usize)) -> Option<&T> {
+        if row < self.height && col < self.width {
+            Some(&self.cells[row * self.width + col])
+        } else {
+            None
+        }
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = (usize, usize)> {
+        let width = self.width;
+        (0..self.height).flat_map(move |row| (0..width).map(move |col| (row, col)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.cells.iter_mut()
+    }
+
+    pub fn neighbors<'a>(
+        &self,
+        (row, col): (usize, usize),
+        offsets: &'a [(i8, i8)],
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let (height, width) = (self.height, self.width);
+        offsets.iter().filter_map(move |offset| {
+            let (r, c) = (
+                row.wrapping_add(offset.0 as usize),
+                col.wrapping_add(offset.1 as usize),
+            );
+            if r < height && c < width {
+                Some((r, c))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+}
+
+impl Grid<usize> {
+    pub fn parse_digits(content: &str) -> Result<Self, String> {
+        let rows = content
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .map(|c| {
+                        c.to_digit(10)
+                            .map(|digit| digit as usize)
+                            .ok_or_else(|| format!("Invalid digit '{}'", c))
+                    })
+                    .collect::<Result<Vec<usize>, String>>()
+            })
+            .collect::<Result<Vec<Vec<usize>>, String>>()?;
+        Self::from_rows(rows)
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (row, col): (usize, usize)) -> &Self::Output {
+        assert!(row < self.height && col < self.width, "Out of the grid");
+        &self.cells[row * self.width + col]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut Self::Output {
+        assert!(row < self.height && col < self.width, "Out of the grid");
+        &mut self.cells[row * self.width + col]
+    }
+}
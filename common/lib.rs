@@ -1,2 +1,6 @@
+pub mod automaton;
+pub mod convex;
 pub mod coords;
+pub mod grid;
+pub mod ocr;
 pub mod utility;
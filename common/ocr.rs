@@ -0,0 +1,247 @@
+use std::collections::HashSet;
+
+use crate::coords::{Coords, CoordsImpl};
+
+// Block letters used by Advent of Code puzzles, '#' is a dot. Letters are separated by at least
+// one empty column.
+
+const SMALL_LETTERS: [(char, [&str; 6]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', ["###", ".#.", ".#.", ".#.", ".#.", "###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+const LARGE_LETTERS: [(char, [&str; 10]); 15] = [
+    (
+        'A',
+        [
+            "..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'B',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#",
+            "#....#", "#####.",
+        ],
+    ),
+    (
+        'C',
+        [
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#....#", ".####.",
+        ],
+    ),
+    (
+        'E',
+        [
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'F',
+        [
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'G',
+        [
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#",
+            "#...##", ".###.#",
+        ],
+    ),
+    (
+        'H',
+        [
+            "#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'J',
+        [
+            "...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.",
+            "#...#.", ".###..",
+        ],
+    ),
+    (
+        'K',
+        [
+            "#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..",
+            "#...#.", "#....#",
+        ],
+    ),
+    (
+        'L',
+        [
+            "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'N',
+        [
+            "#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##",
+            "#...##", "#....#",
+        ],
+    ),
+    (
+        'P',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'R',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'X',
+        [
+            "#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'Z',
+        [
+            "######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+];
+
+// Renders the dots with '#', the empty cells with '.', starting from the top left dot
+pub fn render(dots: &HashSet<Coords>) -> String {
+    let min_x = dots.iter().map(|c| c.x()).min().unwrap_or(0);
+    let min_y = dots.iter().map(|c| c.y()).min().unwrap_or(0);
+    let max_x = dots.iter().map(|c| c.x()).max().unwrap_or(0);
+    let max_y = dots.iter().map(|c| c.y()).max().unwrap_or(0);
+    (min_y..=max_y)
+        .map(|y| {
+            (min_x..=max_x)
+                .map(|x| if dots.contains(&[x, y]) { '#' } else { '.' })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn find_letter<const HEIGHT: usize>(
+    letters: &[(char, [&str; HEIGHT])],
+    glyph: &[String],
+) -> Option<char> {
+    letters
+        .iter()
+        .find(|(_letter, rows)| rows.iter().zip(glyph.iter()).all(|(lhs, rhs)| lhs == rhs))
+        .map(|&(letter, _rows)| letter)
+}
+
+// Recognizes the letters formed by the dots. If the text has an unknown height or glyph, the
+// rendered dots are returned as error.
+pub fn recognize(dots: &HashSet<Coords>) -> Result<String, String> {
+    let image = render(dots);
+    let rows = image.lines().collect::<Vec<&str>>();
+    let width = rows.first().map_or(0, |row| row.len());
+    let is_empty_column = |col: usize| rows.iter().all(|row| row.as_bytes()[col] == b'.');
+
+    let mut text = String::new();
+    let mut col = 0;
+    while col < width {
+        if is_empty_column(col) {
+            col += 1;
+            continue;
+        }
+        let glyph_start = col;
+        while col < width && !is_empty_column(col) {
+            col += 1;
+        }
+        let glyph = rows
+            .iter()
+            .map(|row| row[glyph_start..col].to_string())
+            .collect::<Vec<String>>();
+        let letter = match rows.len() {
+            6 => find_letter(&SMALL_LETTERS, &glyph),
+            10 => find_letter(&LARGE_LETTERS, &glyph),
+            _ => None,
+        };
+        text.push(letter.ok_or_else(|| image.clone())?);
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Dots of the glyphs placed side by side, separated by an empty column
+    fn dots<const HEIGHT: usize>(glyphs: &[[&str; HEIGHT]]) -> HashSet<Coords> {
+        let mut dots = HashSet::new();
+        let mut left = 0;
+        for glyph in glyphs {
+            for (y, row) in glyph.iter().enumerate() {
+                for (x, c) in row.chars().enumerate() {
+                    if c == '#' {
+                        dots.insert([left + x, y]);
+                    }
+                }
+            }
+            left += glyph[0].len() + 1;
+        }
+        dots
+    }
+
+    #[test]
+    fn recognizes_every_small_letter() {
+        for (letter, glyph) in SMALL_LETTERS {
+            assert_eq!(recognize(&dots(&[glyph])), Ok(letter.to_string()));
+        }
+    }
+
+    #[test]
+    fn recognizes_every_large_letter() {
+        for (letter, glyph) in LARGE_LETTERS {
+            assert_eq!(recognize(&dots(&[glyph])), Ok(letter.to_string()));
+        }
+    }
+
+    #[test]
+    fn recognizes_words() {
+        let glyphs = SMALL_LETTERS.map(|(_letter, glyph)| glyph);
+        let text = SMALL_LETTERS.map(|(letter, _glyph)| letter);
+        assert_eq!(recognize(&dots(&glyphs)), Ok(text.iter().collect()));
+        let glyphs = LARGE_LETTERS.map(|(_letter, glyph)| glyph);
+        let text = LARGE_LETTERS.map(|(letter, _glyph)| letter);
+        assert_eq!(recognize(&dots(&glyphs)), Ok(text.iter().collect()));
+    }
+
+    #[test]
+    fn unknown_glyph_is_rendered() {
+        let dots = dots(&[["#..", ".#.", "..#", ".#.", "#..", "###"]]);
+        assert_eq!(recognize(&dots), Err(render(&dots)));
+        assert_eq!(render(&dots), "#..\n.#.\n..#\n.#.\n#..\n###");
+    }
+}